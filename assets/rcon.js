@@ -0,0 +1,38 @@
+// Fetches the live parts of the page again when the server reports a change over /events.
+// Elements opt in with `hx-trigger="sse:players"`, `hx-trigger="sse:bans"` or `hx-trigger="sse:ip_bans"`,
+// inside an element with `data-rcon-events` set to the event stream URL.
+(() => {
+    let source = null;
+
+    htmx.onLoad(() => {
+        const container = document.querySelector("[data-rcon-events]");
+        if (source || !container) {
+            return;
+        }
+
+        // One connection per page, it survives htmx swapping the body.
+        source = new EventSource(container.dataset.rconEvents);
+        for (const name of ["players", "bans", "ip_bans"]) {
+            source.addEventListener(name, () => {
+                for (const element of document.querySelectorAll(`[hx-trigger*="sse:${name}"]`)) {
+                    htmx.trigger(element, `sse:${name}`);
+                }
+            });
+        }
+    });
+})();
+
+// Switches between the light and the dark theme, remembered in local storage.
+// Without a saved choice the page follows the system preference.
+document.addEventListener("click", (event) => {
+    if (!event.target.closest("[data-rcon-theme-toggle]")) {
+        return;
+    }
+
+    const root = document.documentElement;
+    const dark = root.dataset.theme
+        ? root.dataset.theme === "dark"
+        : window.matchMedia("(prefers-color-scheme: dark)").matches;
+    root.dataset.theme = dark ? "light" : "dark";
+    localStorage.setItem("rcon-theme", root.dataset.theme);
+});
@@ -1,31 +1,125 @@
-use bevy::prelude::*;
-use bevy_rcon::{RconPlayer, RconPlayers, RconPlugin, DbRconBannedPlayer};
+use bevy::{log::LogPlugin, prelude::*, time::common_conditions::on_real_timer};
+use bevy_defer::{AsyncAccess, AsyncWorld};
+use bevy_rcon::{
+    hash_password, rcon_log_layer, DbRconBannedPlayer, RconAppExt, RconCommand, RconPage, RconPageRequest,
+    RconPermission, RconPlayer, RconPlayerAction, RconPlayerActionEvent, RconPlayers, RconPlugin, RconRole,
+};
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 
 fn main() {
     let mut app = App::new();
     app.add_plugins((
-        DefaultPlugins,
+        // Captures the logs, so they can be read on the logs page of the panel.
+        DefaultPlugins.set(LogPlugin {
+            custom_layer: rcon_log_layer,
+            ..default()
+        }),
         WorldInspectorPlugin::new(),
-        RconPlugin,
+        RconPlugin::new()
+            .bind("127.0.0.1:8080")
+            .game_name("Basic Example")
+            .server_name("Local Server")
+            .accent_color("#e0513b")
+            .footer_link("Moderation guidelines", "https://example.com/guidelines")
+            // Flags players who connect with the IP address or hardware ID of a banned account.
+            .detect_ban_evasion(true)
+            // Log in with admin/admin. Real servers should store a precomputed hash instead.
+            .admin("admin", hash_password("admin"), RconRole::SuperAdmin),
     ));
     app.register_type::<RconPlayer>();
     app.register_type::<DbRconBannedPlayer>();
     app.add_systems(Startup, setup_players);
+    app.add_rcon_command(
+        RconCommand::new("set_time_scale")
+            .help("Sets the speed of the game clock")
+            .usage("<scale>"),
+        |world: &mut World, scale: f32| {
+            world.resource_mut::<Time<Virtual>>().set_relative_speed(scale);
+            format!("Time scale set to {}", scale)
+        },
+    );
+    app.add_rcon_page(
+        "/clock",
+        RconPage::new("Clock").permission(RconPermission::RunCommands),
+        clock_page,
+    );
+    app.add_rcon_player_action::<Mute>(
+        RconPlayerAction::new("mute")
+            .label("Mute")
+            .number_field("minutes", "Minutes")
+            .text_field("reason", "Reason (optional)"),
+    );
+    app.add_systems(Update, mute_players);
+    app.add_systems(
+        Update,
+        update_session_lengths.run_if(on_real_timer(std::time::Duration::from_secs(1))),
+    );
     app.run();
 }
 
+/// The form data of the mute button in the player list.
+#[derive(serde::Deserialize)]
+struct Mute {
+    minutes: u32,
+    reason: Option<String>,
+}
+
+fn mute_players(mut events: EventReader<RconPlayerActionEvent<Mute>>) {
+    for event in events.read() {
+        info!(
+            "Muting {} for {} minutes, reason: {}",
+            event.player,
+            event.data.minutes,
+            event.data.reason.as_deref().unwrap_or("none")
+        );
+    }
+}
+
+/// A custom page that shows the game clock, with a form to change its speed.
+async fn clock_page(request: RconPageRequest) -> maud::Markup {
+    let time = AsyncWorld.resource::<Time<Virtual>>();
+    if let Some(scale) = request.params.get("scale").and_then(|scale| scale.parse::<f32>().ok()) {
+        time.get_mut(|time| time.set_relative_speed(scale)).ok();
+    }
+    let (elapsed, speed) = time
+        .get(|time| (time.elapsed_secs(), time.relative_speed()))
+        .unwrap_or_default();
+
+    maud::html! {
+        h3 { "Clock" }
+        p { "Elapsed: " (format!("{:.1}", elapsed)) "s, running at " (speed) "x" }
+        form method="post" action="/clock" {
+            input type="number" name="scale" step="0.1" min="0" value=(speed);
+            button type="submit" { "Set speed" }
+        }
+    }
+}
+
 fn setup_players(mut rcon_players: ResMut<RconPlayers>) {
-    rcon_players.players.push(RconPlayer {
-        unique_id: "steam_123".to_string(),
-        name: "Player1".to_string(),
-    });
-    rcon_players.players.push(RconPlayer {
-        unique_id: "steam_456".to_string(),
-        name: "Player2".to_string(),
-    });
-    rcon_players.players.push(RconPlayer {
-        unique_id: "steam_789".to_string(),
-        name: "Player3".to_string(),
-    });
+    // The metadata is shown as sortable columns in the player list.
+    rcon_players.players.push(
+        RconPlayer::new("steam_123", "Player1")
+            .with_ip("203.0.113.7".parse().unwrap())
+            .with_metadata("team", "Red")
+            .with_metadata("ping", 35),
+    );
+    rcon_players.players.push(
+        RconPlayer::new("steam_456", "Player2")
+            .with_ip("198.51.100.23".parse().unwrap())
+            .with_metadata("team", "Blue")
+            .with_metadata("ping", 120),
+    );
+    rcon_players.players.push(
+        RconPlayer::new("steam_789", "Player3")
+            .with_ip("2001:db8::42".parse().unwrap())
+            .with_metadata("team", "Red")
+            .with_metadata("ping", 64),
+    );
+}
+
+/// Keeps the session length of every player up to date.
+fn update_session_lengths(time: Res<Time<Real>>, mut rcon_players: ResMut<RconPlayers>) {
+    for player in rcon_players.players.iter_mut() {
+        player.set_metadata("session", time.elapsed());
+    }
 }
@@ -0,0 +1,282 @@
+use bevy::prelude::*;
+
+use crate::{
+    audit, entities, RconConfig, RconPlayerRemoved, DbRconBanDetails, DbRconBanExpiry, DbRconBannedPlayer, RconBanOptions, RconActor, RconPlayer,
+    RconPlayerActionEvent, RconPlayerBanned, RconPlayerKicked, RconPlayerUnbanned, RconPlayers, DbRconIpBan,
+    RconIpBanned, RconIpRange, RconIpUnbanned, RconPlayerComponent,
+};
+
+/// The reasons an admin action can fail.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RconActionError {
+    /// The player data submitted with the action is incomplete.
+    InvalidPlayer,
+    /// No connected player has the given unique ID.
+    PlayerNotFound(String),
+    /// A ban already exists for the given unique ID.
+    AlreadyBanned(String),
+    /// No ban exists for the given unique ID.
+    NotBanned(String),
+    /// A ban already exists for the given IP range.
+    IpAlreadyBanned(RconIpRange),
+    /// No ban exists for the given IP range.
+    IpNotBanned(RconIpRange),
+}
+
+impl std::fmt::Display for RconActionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RconActionError::InvalidPlayer => write!(f, "Invalid player data"),
+            RconActionError::PlayerNotFound(id) => write!(f, "No connected player with ID {}", id),
+            RconActionError::AlreadyBanned(id) => write!(f, "Player with ID {} is already banned", id),
+            RconActionError::NotBanned(id) => write!(f, "Player with ID {} is not banned", id),
+            RconActionError::IpAlreadyBanned(range) => write!(f, "{} is already banned", range),
+            RconActionError::IpNotBanned(range) => write!(f, "{} is not banned", range),
+        }
+    }
+}
+
+impl std::error::Error for RconActionError {}
+
+/// Removes a player from the player list and sends a `RconPlayerKicked` event.
+pub(crate) fn kick_player(
+    world: &mut World,
+    actor: &RconActor,
+    unique_id: &str,
+    reason: Option<String>,
+) -> Result<RconPlayer, RconActionError> {
+    let (player, entity) = remove_player(world, unique_id, RconPlayerRemoved::Kicked)
+        .ok_or_else(|| RconActionError::PlayerNotFound(unique_id.to_string()))?;
+
+    info!("{} kicked player {}", actor.admin, player);
+    audit::record(world, actor, "kick", Some(&player), reason.clone());
+    world.send_event(RconPlayerKicked { player: player.clone(), reason, entity });
+
+    Ok(player)
+}
+
+/// Adds a player to the banned list, removes them from the player list and sends a `RconPlayerBanned` event.
+/// The player does not have to be connected, so that players can be banned by ID.
+pub(crate) fn ban_player(
+    world: &mut World,
+    actor: &RconActor,
+    player: RconPlayer,
+    options: RconBanOptions,
+) -> Result<RconPlayer, RconActionError> {
+    if player.unique_id.is_empty() || player.name.is_empty() {
+        return Err(RconActionError::InvalidPlayer);
+    }
+
+    if find_ban(world, &player.unique_id).is_some() {
+        return Err(RconActionError::AlreadyBanned(player.unique_id));
+    }
+
+    let expiry = options.duration.map(DbRconBanExpiry::from_now);
+    let mut ban = world.spawn((
+        DbRconBannedPlayer {
+            unique_id: player.unique_id.clone(),
+            name: player.name.clone(),
+        },
+        DbRconBanDetails {
+            reason: options.reason.clone(),
+            issued_by: actor.admin.clone(),
+            created_at: audit::unix_now(),
+            notes: options.notes,
+        },
+    ));
+    if let Some(expiry) = expiry.clone() {
+        ban.insert(expiry);
+    }
+    let entity = remove_player(world, &player.unique_id, RconPlayerRemoved::Banned).and_then(|(_, entity)| entity);
+
+    info!("{} banned player {}", actor.admin, player);
+    audit::record(world, actor, "ban", Some(&player), options.reason.clone());
+    world.send_event(RconPlayerBanned {
+        player: player.clone(),
+        expires_at: expiry.map(|expiry| expiry.expires_at),
+        reason: options.reason,
+        entity,
+    });
+
+    Ok(player)
+}
+
+/// Removes a player from the banned list and sends a `RconPlayerUnbanned` event.
+pub(crate) fn unban_player(
+    world: &mut World,
+    actor: &RconActor,
+    unique_id: &str,
+) -> Result<RconPlayer, RconActionError> {
+    let (entity, banned) = find_ban(world, unique_id)
+        .ok_or_else(|| RconActionError::NotBanned(unique_id.to_string()))?;
+    world.despawn(entity);
+
+    let player = RconPlayer::new(banned.unique_id, banned.name);
+
+    info!("{} unbanned player {}", actor.admin, player);
+    audit::record(world, actor, "unban", Some(&player), None);
+    world.send_event(RconPlayerUnbanned { player: player.clone() });
+
+    Ok(player)
+}
+
+/// Adds an IP ban, removes the connected players in the range and sends a `RconIpBanned` event.
+pub(crate) fn ban_ip(
+    world: &mut World,
+    actor: &RconActor,
+    range: RconIpRange,
+    options: RconBanOptions,
+) -> Result<DbRconIpBan, RconActionError> {
+    if find_ip_ban(world, range).is_some() {
+        return Err(RconActionError::IpAlreadyBanned(range));
+    }
+
+    let ban = DbRconIpBan {
+        range,
+        reason: options.reason.clone(),
+        issued_by: actor.admin.clone(),
+        created_at: audit::unix_now(),
+        notes: options.notes,
+        expires_at: options.duration.map(|duration| audit::unix_now().saturating_add(duration.as_secs())),
+    };
+    world.spawn(ban.clone());
+
+    // Players can be listed in `RconPlayers` and as entities, so they are collected from both by ID.
+    let mut unique_ids: Vec<String> = world
+        .resource::<RconPlayers>()
+        .players
+        .iter()
+        .filter(|player| player.ip.is_some_and(|ip| range.contains(ip)))
+        .map(|player| player.unique_id.clone())
+        .collect();
+    let mut player_entities = world.query_filtered::<&RconPlayerComponent, Without<RconPlayerRemoved>>();
+    for player in player_entities.iter(world) {
+        if player.ip.is_some_and(|ip| range.contains(ip)) && !unique_ids.contains(&player.unique_id) {
+            unique_ids.push(player.unique_id.clone());
+        }
+    }
+
+    let mut players = vec![];
+    let mut entities = vec![];
+    for unique_id in unique_ids {
+        if let Some((player, entity)) = remove_player(world, &unique_id, RconPlayerRemoved::Banned) {
+            players.push(player);
+            entities.extend(entity);
+        }
+    }
+
+    info!("{} banned IP {}, removing {} connected players", actor.admin, range, players.len());
+    audit::record_ip(world, actor, "ban_ip", range, options.reason);
+    world.send_event(RconIpBanned {
+        ban: ban.clone(),
+        players,
+        entities,
+    });
+
+    Ok(ban)
+}
+
+/// Removes an IP ban and sends a `RconIpUnbanned` event.
+pub(crate) fn unban_ip(
+    world: &mut World,
+    actor: &RconActor,
+    range: RconIpRange,
+) -> Result<DbRconIpBan, RconActionError> {
+    let (entity, ban) = find_ip_ban(world, range).ok_or(RconActionError::IpNotBanned(range))?;
+    world.despawn(entity);
+
+    info!("{} unbanned IP {}", actor.admin, range);
+    audit::record_ip(world, actor, "unban_ip", range, None);
+    world.send_event(RconIpUnbanned { range });
+
+    Ok(ban)
+}
+
+/// Sends a `RconPlayerActionEvent` for a custom player action. The player stays connected,
+/// the game decides what the action does. `summary` is written to the audit log as the reason.
+pub(crate) fn player_action<T: Send + Sync + 'static>(
+    world: &mut World,
+    actor: &RconActor,
+    action: &str,
+    unique_id: &str,
+    summary: Option<String>,
+    data: T,
+) -> Result<RconPlayer, RconActionError> {
+    let entity = entities::find_player_entity(world, unique_id);
+    let player = world
+        .resource::<RconPlayers>()
+        .players
+        .iter()
+        .find(|player| player.unique_id == unique_id)
+        .cloned()
+        .or_else(|| entity.as_ref().map(|(_, player)| player.clone()))
+        .ok_or_else(|| RconActionError::PlayerNotFound(unique_id.to_string()))?;
+
+    info!("{} performed {} on player {}", actor.admin, action, player);
+    audit::record(world, actor, action, Some(&player), summary);
+    world.send_event(RconPlayerActionEvent {
+        action: action.to_string(),
+        player: player.clone(),
+        actor: actor.clone(),
+        entity: entity.map(|(entity, _)| entity),
+        data,
+    });
+
+    Ok(player)
+}
+
+/// The connected player with the given unique ID. Players that are not connected get their ID as name,
+/// so that they can still be banned by ID.
+pub(crate) fn player_by_id(world: &World, unique_id: &str) -> RconPlayer {
+    world
+        .resource::<RconPlayers>()
+        .players
+        .iter()
+        .find(|player| player.unique_id == unique_id)
+        .cloned()
+        .unwrap_or_else(|| RconPlayer::new(unique_id, unique_id))
+}
+
+/// Finds the ban entity for the given unique ID, if any.
+fn find_ban(world: &mut World, unique_id: &str) -> Option<(Entity, DbRconBannedPlayer)> {
+    let mut banned_players = world.query::<(Entity, &DbRconBannedPlayer)>();
+    banned_players
+        .iter(world)
+        .find(|(_, banned)| banned.unique_id == unique_id)
+        .map(|(entity, banned)| (entity, banned.clone()))
+}
+
+/// Finds the IP ban entity for exactly the given range, if any.
+fn find_ip_ban(world: &mut World, range: RconIpRange) -> Option<(Entity, DbRconIpBan)> {
+    let mut ip_bans = world.query::<(Entity, &DbRconIpBan)>();
+    ip_bans
+        .iter(world)
+        .find(|(_, ban)| ban.range == range)
+        .map(|(entity, ban)| (entity, ban.clone()))
+}
+
+/// Removes a player from the player list, and despawns or tags their entity if player entities are enabled.
+/// Returns the player and their entity if they were connected.
+fn remove_player(
+    world: &mut World,
+    unique_id: &str,
+    removed: RconPlayerRemoved,
+) -> Option<(RconPlayer, Option<Entity>)> {
+    let listed = {
+        let mut players = world.resource_mut::<RconPlayers>();
+        let position = players.players.iter().position(|player| player.unique_id == unique_id);
+        position.map(|position| players.players.remove(position))
+    };
+
+    let entity = entities::find_player_entity(world, unique_id);
+    if let (Some((entity, _)), Some(mode)) = (&entity, world.resource::<RconConfig>().player_entities) {
+        entities::remove_player_entity(&mut world.commands(), mode, *entity, removed);
+        world.flush();
+    }
+
+    match (listed, entity) {
+        (Some(player), entity) => Some((player, entity.map(|(entity, _)| entity))),
+        (None, Some((entity, player))) => Some((player, Some(entity))),
+        (None, None) => None,
+    }
+}
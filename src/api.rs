@@ -0,0 +1,334 @@
+//! A JSON API under `/api/v1` that mirrors the HTML panel, for scripts and bots.
+//! It uses the same sessions and permissions as the panel.
+
+use axum::{
+    extract::{
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+        Path, Query,
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use bevy::prelude::*;
+use bevy_defer::{AsyncAccess, AsyncWorld};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions,
+    audit::{self, AuditQuery},
+    metadata::PlayerSort,
+    parse_duration, DbRconAuditEntry, DbRconBanDetails, DbRconBanExpiry, DbRconBannedPlayer, DbRconIpBan,
+    RconActionError, RconActor, RconAdmin, RconBanInfo, RconBanOptions, RconCaller, RconCommandError, RconCommands,
+    RconForbidden, RconInvalidIpRange, RconIpRange, RconPermission, RconPlayer, RconPlayers,
+};
+
+/// An error response, sent as `{"error": "..."}` with a matching status code.
+pub(crate) struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    pub(crate) fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = serde_json::json!({ "error": self.message });
+        (self.status, Json(body)).into_response()
+    }
+}
+
+impl From<RconForbidden> for ApiError {
+    fn from(forbidden: RconForbidden) -> Self {
+        ApiError::new(
+            StatusCode::FORBIDDEN,
+            format!("You do not have permission to {}.", forbidden.0),
+        )
+    }
+}
+
+impl From<RconActionError> for ApiError {
+    fn from(error: RconActionError) -> Self {
+        let status = match error {
+            RconActionError::InvalidPlayer => StatusCode::BAD_REQUEST,
+            RconActionError::PlayerNotFound(_)
+            | RconActionError::NotBanned(_)
+            | RconActionError::IpNotBanned(_) => StatusCode::NOT_FOUND,
+            RconActionError::AlreadyBanned(_) | RconActionError::IpAlreadyBanned(_) => StatusCode::CONFLICT,
+        };
+        ApiError::new(status, error.to_string())
+    }
+}
+
+impl From<RconCommandError> for ApiError {
+    fn from(error: RconCommandError) -> Self {
+        let status = match error {
+            RconCommandError::UnknownCommand(_) => StatusCode::NOT_FOUND,
+            RconCommandError::Forbidden(_) => StatusCode::FORBIDDEN,
+            RconCommandError::Failed(_) => StatusCode::BAD_REQUEST,
+        };
+        ApiError::new(status, error.to_string())
+    }
+}
+
+impl From<RconInvalidIpRange> for ApiError {
+    fn from(error: RconInvalidIpRange) -> Self {
+        ApiError::new(StatusCode::BAD_REQUEST, error.to_string())
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::new(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::new(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::new(rejection.status(), rejection.body_text())
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+#[derive(Deserialize)]
+pub(crate) struct ApiBanRequest {
+    unique_id: String,
+    /// Defaults to the name of the connected player, or the unique ID if they are not connected.
+    name: Option<String>,
+    /// A duration like `30m` or `7d`, see `parse_duration`. Omit for a permanent ban.
+    duration: Option<String>,
+    reason: Option<String>,
+    notes: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ApiIpBanRequest {
+    /// An IP address or CIDR range, like `203.0.113.7` or `203.0.113.0/24`.
+    range: String,
+    /// A duration like `30m` or `7d`, see `parse_duration`. Omit for a permanent ban.
+    duration: Option<String>,
+    reason: Option<String>,
+    notes: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ApiKickRequest {
+    unique_id: String,
+    reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ApiCommandRequest {
+    command: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ApiCommandResponse {
+    output: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ApiAuditResponse {
+    entries: Vec<DbRconAuditEntry>,
+    page: usize,
+    page_count: usize,
+}
+
+/// `GET /api/v1/players`, sorted by `?sort=<name, unique_id or metadata key>&desc=true` if given.
+/// IP addresses and hardware IDs are only included for admins who can ban.
+pub(crate) async fn players(
+    admin: axum::Extension<RconAdmin>,
+    sort: Result<Query<PlayerSort>, QueryRejection>,
+) -> ApiResult<Vec<RconPlayer>> {
+    admin.require(RconPermission::ViewPlayers)?;
+    let sort = sort?;
+    sort.require(&admin)?;
+
+    let mut players = AsyncWorld
+        .resource::<RconPlayers>()
+        .get_mut(|players| players.players.clone())
+        .unwrap_or_default();
+    sort.apply(&mut players);
+
+    Ok(Json(players.into_iter().map(|player| player.visible_to(admin.role)).collect()))
+}
+
+/// `GET /api/v1/bans`
+pub(crate) async fn bans(admin: axum::Extension<RconAdmin>) -> ApiResult<Vec<RconBanInfo>> {
+    admin.require(RconPermission::ViewPlayers)?;
+
+    let bans = AsyncWorld
+        .query::<(&DbRconBannedPlayer, Option<&DbRconBanExpiry>, Option<&DbRconBanDetails>)>()
+        .get_mut(|mut query| {
+            query
+                .iter()
+                .map(|(banned, expiry, details)| RconBanInfo {
+                    banned: banned.clone(),
+                    expiry: expiry.cloned(),
+                    details: details.cloned(),
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    Ok(Json(bans))
+}
+
+/// `POST /api/v1/bans`, responds with the new ban.
+pub(crate) async fn ban(
+    admin: axum::Extension<RconAdmin>,
+    actor: RconActor,
+    request: Result<Json<ApiBanRequest>, JsonRejection>,
+) -> Result<(StatusCode, Json<RconBanInfo>), ApiError> {
+    admin.require(RconPermission::Ban)?;
+    let ApiBanRequest { unique_id, name, duration, reason, notes } = request?.0;
+
+    let duration = match duration {
+        Some(input) => Some(parse_duration(&input).ok_or_else(|| {
+            ApiError::new(StatusCode::BAD_REQUEST, format!("Invalid ban duration: {}", input))
+        })?),
+        None => None,
+    };
+    let options = RconBanOptions { duration, reason, notes };
+
+    let ban = AsyncWorld.run(move |world: &mut World| {
+        let mut player = actions::player_by_id(world, &unique_id);
+        if let Some(name) = name {
+            player.name = name;
+        }
+        let player = actions::ban_player(world, &actor, player, options)?;
+        Ok::<_, RconActionError>(ban_info(world, &player.unique_id))
+    })?;
+
+    // The ban was just created, so it can only be missing if something removed it in between.
+    let ban = ban.ok_or_else(|| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "The ban was removed"))?;
+    Ok((StatusCode::CREATED, Json(ban)))
+}
+
+/// `DELETE /api/v1/bans/{unique_id}`, responds with the unbanned player.
+pub(crate) async fn unban(
+    admin: axum::Extension<RconAdmin>,
+    actor: RconActor,
+    path: Result<Path<String>, PathRejection>,
+) -> ApiResult<RconPlayer> {
+    admin.require(RconPermission::Unban)?;
+    let unique_id = path?.0;
+
+    let player = AsyncWorld.run(move |world: &mut World| actions::unban_player(world, &actor, &unique_id))?;
+    Ok(Json(player))
+}
+
+/// `GET /api/v1/ip_bans`, only for admins who can ban, like the addresses of players.
+pub(crate) async fn ip_bans(admin: axum::Extension<RconAdmin>) -> ApiResult<Vec<DbRconIpBan>> {
+    admin.require(RconPermission::Ban)?;
+
+    let ip_bans = AsyncWorld
+        .query::<&DbRconIpBan>()
+        .get_mut(|mut query| query.iter().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    Ok(Json(ip_bans))
+}
+
+/// `POST /api/v1/ip_bans`, responds with the new ban.
+pub(crate) async fn ban_ip(
+    admin: axum::Extension<RconAdmin>,
+    actor: RconActor,
+    request: Result<Json<ApiIpBanRequest>, JsonRejection>,
+) -> Result<(StatusCode, Json<DbRconIpBan>), ApiError> {
+    admin.require(RconPermission::Ban)?;
+    let ApiIpBanRequest { range, duration, reason, notes } = request?.0;
+
+    let range: RconIpRange = range.parse()?;
+    let duration = match duration {
+        Some(input) => Some(parse_duration(&input).ok_or_else(|| {
+            ApiError::new(StatusCode::BAD_REQUEST, format!("Invalid ban duration: {}", input))
+        })?),
+        None => None,
+    };
+    let options = RconBanOptions { duration, reason, notes };
+
+    let ban = AsyncWorld.run(move |world: &mut World| actions::ban_ip(world, &actor, range, options))?;
+    Ok((StatusCode::CREATED, Json(ban)))
+}
+
+/// `DELETE /api/v1/ip_bans/{range}`, e.g. `/api/v1/ip_bans/203.0.113.0/24`, responds with the lifted ban.
+pub(crate) async fn unban_ip(
+    admin: axum::Extension<RconAdmin>,
+    actor: RconActor,
+    path: Result<Path<String>, PathRejection>,
+) -> ApiResult<DbRconIpBan> {
+    admin.require(RconPermission::Unban)?;
+    let range: RconIpRange = path?.0.parse()?;
+
+    let ban = AsyncWorld.run(move |world: &mut World| actions::unban_ip(world, &actor, range))?;
+    Ok(Json(ban))
+}
+
+/// `POST /api/v1/kick`, responds with the kicked player.
+pub(crate) async fn kick(
+    admin: axum::Extension<RconAdmin>,
+    actor: RconActor,
+    request: Result<Json<ApiKickRequest>, JsonRejection>,
+) -> ApiResult<RconPlayer> {
+    admin.require(RconPermission::Kick)?;
+    let ApiKickRequest { unique_id, reason } = request?.0;
+
+    let player = AsyncWorld.run(move |world: &mut World| {
+        actions::kick_player(world, &actor, &unique_id, reason)
+    })?;
+    Ok(Json(player))
+}
+
+/// `GET /api/v1/audit`, takes the same filters as the audit page.
+pub(crate) async fn audit(
+    admin: axum::Extension<RconAdmin>,
+    query: Result<Query<AuditQuery>, QueryRejection>,
+) -> ApiResult<ApiAuditResponse> {
+    admin.require(RconPermission::ViewAudit)?;
+    let query = query?;
+
+    let (entries, page, page_count) = audit::query_entries(&query);
+    Ok(Json(ApiAuditResponse { entries, page, page_count }))
+}
+
+/// `POST /api/v1/commands`, runs a console command. Failed commands respond with 400 and their error.
+pub(crate) async fn command(
+    admin: axum::Extension<RconAdmin>,
+    actor: RconActor,
+    request: Result<Json<ApiCommandRequest>, JsonRejection>,
+) -> ApiResult<ApiCommandResponse> {
+    let command = request?.0.command;
+    let caller = RconCaller {
+        actor,
+        role: admin.role,
+    };
+
+    let output = AsyncWorld.run(move |world: &mut World| RconCommands::execute(world, &caller, &command))?;
+    Ok(Json(ApiCommandResponse { output }))
+}
+
+fn ban_info(world: &mut World, unique_id: &str) -> Option<RconBanInfo> {
+    let mut bans = world.query::<(&DbRconBannedPlayer, Option<&DbRconBanExpiry>, Option<&DbRconBanDetails>)>();
+    bans.iter(world)
+        .find(|(banned, _, _)| banned.unique_id == unique_id)
+        .map(|(banned, expiry, details)| RconBanInfo {
+            banned: banned.clone(),
+            expiry: expiry.cloned(),
+            details: details.cloned(),
+        })
+}
@@ -0,0 +1,87 @@
+//! Static files embedded in the binary, so the panel works without internet access.
+//! They are served under `/static/` with a content hash in the file name, so they can be cached forever.
+
+use std::sync::LazyLock;
+
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+/// The path all static files are served under.
+pub(crate) const STATIC_PATH: &str = "/static/";
+
+struct Asset {
+    /// The file name without the hash, e.g. `rcon.js`.
+    name: &'static str,
+    content_type: &'static str,
+    bytes: &'static [u8],
+}
+
+const ASSETS: &[Asset] = &[
+    // htmx 1.9.12, unmodified, under the Zero-Clause BSD license.
+    Asset {
+        name: "htmx.min.js",
+        content_type: "text/javascript; charset=utf-8",
+        bytes: include_bytes!("../assets/htmx.min.js"),
+    },
+    Asset {
+        name: "rcon.js",
+        content_type: "text/javascript; charset=utf-8",
+        bytes: include_bytes!("../assets/rcon.js"),
+    },
+    Asset {
+        name: "rcon.css",
+        content_type: "text/css; charset=utf-8",
+        bytes: include_bytes!("../assets/rcon.css"),
+    },
+];
+
+/// The file names with their content hash, e.g. `rcon.1a2b3c4d.js`, in the same order as `ASSETS`.
+static HASHED_NAMES: LazyLock<Vec<String>> = LazyLock::new(|| {
+    ASSETS
+        .iter()
+        .map(|asset| {
+            let hash: String = Sha256::digest(asset.bytes)
+                .iter()
+                .take(4)
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            match asset.name.split_once('.') {
+                Some((stem, extension)) => format!("{}.{}.{}", stem, hash, extension),
+                None => format!("{}.{}", asset.name, hash),
+            }
+        })
+        .collect()
+});
+
+/// The URL of an embedded file, e.g. `static_url("rcon.js")`.
+///
+/// # Panics
+/// Panics if no file with that name is embedded.
+pub(crate) fn static_url(name: &str) -> String {
+    let index = ASSETS
+        .iter()
+        .position(|asset| asset.name == name)
+        .unwrap_or_else(|| panic!("No embedded asset named {}", name));
+    format!("{}{}", STATIC_PATH, HASHED_NAMES[index])
+}
+
+/// Serves an embedded file by its hashed name. Old hashes are not found, the page links the current ones.
+pub(crate) async fn serve_static(path: axum::extract::Path<String>) -> Response {
+    let Some(index) = HASHED_NAMES.iter().position(|name| *name == path.0) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let asset = &ASSETS[index];
+    (
+        [
+            (header::CONTENT_TYPE, asset.content_type),
+            // The hash changes with the content, so the file at this URL never does.
+            (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+        ],
+        asset.bytes,
+    )
+        .into_response()
+}
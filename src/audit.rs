@@ -0,0 +1,279 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{
+    extract::FromRequestParts,
+    http::{request::Parts, HeaderMap, StatusCode},
+};
+use bevy::prelude::*;
+use bevy_defer::{AsyncAccess, AsyncWorld};
+use maud::html;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    template::render_page,
+    RconAdmin, RconConfig, RconForbidden, RconIpRange, RconPermission, RconPlayer,
+};
+
+/// How many audit entries are shown per page.
+const AUDIT_PAGE_SIZE: usize = 50;
+
+/// A record of an action performed by an admin, stored in the database.
+/// The target is stored by ID and name rather than as a `RconPlayer`,
+/// so that old entries keep loading when the player data changes shape.
+#[derive(Component, Clone, Default, Serialize, Deserialize, PartialEq, Reflect)]
+pub struct DbRconAuditEntry {
+    /// The username of the admin that performed the action.
+    pub admin: String,
+    /// The action that was performed, e.g. `"ban"`.
+    pub action: String,
+    pub target_id: Option<String>,
+    pub target_name: Option<String>,
+    pub reason: Option<String>,
+    /// Seconds since the unix epoch.
+    pub timestamp: u64,
+    /// The IP address the request came from, if known.
+    pub source_ip: Option<String>,
+}
+
+/// An event that is sent to the plugin user whenever an admin action is recorded in the audit log.
+#[derive(Event, Clone)]
+pub struct RconAuditEvent {
+    pub entry: DbRconAuditEntry,
+}
+
+/// Who performed an action, and from where.
+/// Can be extracted in handlers behind the session middleware.
+#[derive(Clone, Debug)]
+pub struct RconActor {
+    pub admin: String,
+    pub source_ip: Option<String>,
+}
+
+impl RconActor {
+    /// The actor for actions the plugin performs on its own, like lifting expired bans.
+    pub(crate) fn system() -> Self {
+        Self {
+            admin: "system".to_string(),
+            source_ip: None,
+        }
+    }
+
+    /// The actor for actions game code performs through `RconBans`.
+    pub(crate) fn game() -> Self {
+        Self {
+            admin: "game".to_string(),
+            source_ip: None,
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for RconActor {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let admin = parts
+            .extensions
+            .get::<RconAdmin>()
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+
+        // The webserver does not expose the peer address, so this relies on a reverse proxy setting these headers.
+        // Clients can send them too, so they are only read when the proxy is trusted to overwrite them.
+        let trust_proxy_headers = AsyncWorld
+            .resource::<RconConfig>()
+            .get(|config| config.trust_proxy_headers)
+            .unwrap_or(false);
+        let source_ip = trust_proxy_headers.then(|| proxy_source_ip(&parts.headers)).flatten();
+
+        Ok(RconActor {
+            admin: admin.username.clone(),
+            source_ip,
+        })
+    }
+}
+
+/// The client address reported by the reverse proxy: `X-Real-IP`, or else the last `X-Forwarded-For` entry,
+/// which is the one the nearest proxy added. Earlier entries come from the client and can be forged.
+fn proxy_source_ip(headers: &HeaderMap) -> Option<String> {
+    let real_ip = headers.get("x-real-ip").and_then(|value| value.to_str().ok());
+    let forwarded_for = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .last();
+
+    real_ip
+        .or(forwarded_for)
+        .map(|ip| ip.trim().to_string())
+        .filter(|ip| !ip.is_empty())
+}
+
+/// Writes an audit entry to the database and sends a `RconAuditEvent`.
+pub(crate) fn record(
+    world: &mut World,
+    actor: &RconActor,
+    action: &str,
+    target: Option<&RconPlayer>,
+    reason: Option<String>,
+) {
+    let target_id = target.map(|player| player.unique_id.clone());
+    let target_name = target.map(|player| player.name.clone());
+    record_entry(world, actor, action, target_id, target_name, reason);
+}
+
+/// Writes an action on an IP address or range to the audit log, with the range as the target ID.
+pub(crate) fn record_ip(world: &mut World, actor: &RconActor, action: &str, range: RconIpRange, reason: Option<String>) {
+    record_entry(world, actor, action, Some(range.to_string()), None, reason);
+}
+
+fn record_entry(
+    world: &mut World,
+    actor: &RconActor,
+    action: &str,
+    target_id: Option<String>,
+    target_name: Option<String>,
+    reason: Option<String>,
+) {
+    let entry = DbRconAuditEntry {
+        admin: actor.admin.clone(),
+        action: action.to_string(),
+        target_id,
+        target_name,
+        reason,
+        timestamp: unix_now(),
+        source_ip: actor.source_ip.clone(),
+    };
+
+    world.spawn(entry.clone());
+    world.send_event(RconAuditEvent { entry });
+}
+
+/// The current time in seconds since the unix epoch.
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Formats seconds since the unix epoch as a UTC date and time.
+pub(crate) fn format_timestamp(timestamp: u64) -> String {
+    chrono::DateTime::from_timestamp(timestamp as i64, 0)
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_default()
+}
+
+#[derive(Deserialize, Default)]
+pub(crate) struct AuditQuery {
+    #[serde(default)]
+    page: usize,
+    #[serde(default)]
+    admin: String,
+    #[serde(default)]
+    action: String,
+    #[serde(default)]
+    target: String,
+}
+
+impl AuditQuery {
+    fn matches(&self, entry: &DbRconAuditEntry) -> bool {
+        let contains = |value: &str, filter: &str| value.to_lowercase().contains(&filter.to_lowercase());
+
+        (self.admin.is_empty() || contains(&entry.admin, &self.admin))
+            && (self.action.is_empty() || entry.action.eq_ignore_ascii_case(&self.action))
+            && (self.target.is_empty()
+                || entry.target_id.as_deref().is_some_and(|id| contains(id, &self.target))
+                || entry.target_name.as_deref().is_some_and(|name| contains(name, &self.target)))
+    }
+}
+
+/// Returns the requested page of entries matching the filters, newest first,
+/// along with the page number and the number of pages.
+pub(crate) fn query_entries(query: &AuditQuery) -> (Vec<DbRconAuditEntry>, usize, usize) {
+    let mut entries = AsyncWorld
+        .query::<&DbRconAuditEntry>()
+        .get_mut(|mut entries| {
+            entries
+                .iter()
+                .filter(|entry| query.matches(entry))
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.timestamp));
+
+    let page_count = entries.len().div_ceil(AUDIT_PAGE_SIZE).max(1);
+    let page = query.page.min(page_count - 1);
+    let entries = entries
+        .into_iter()
+        .skip(page * AUDIT_PAGE_SIZE)
+        .take(AUDIT_PAGE_SIZE)
+        .collect();
+
+    (entries, page, page_count)
+}
+
+/// Lists the audit log, newest first, with paging and filters.
+pub(crate) async fn audit_page(
+    admin: axum::Extension<RconAdmin>,
+    query: axum::extract::Query<AuditQuery>,
+) -> Result<axum::response::Html<String>, RconForbidden> {
+    admin.require(RconPermission::ViewAudit)?;
+    let query = query.0;
+
+    let (entries, page, page_count) = query_entries(&query);
+
+    let content = html! {
+        h3 { "Audit Log" }
+        form method="get" action="/audit" {
+            input type="text" name="admin" placeholder="Admin" value=(query.admin);
+            input type="text" name="action" placeholder="Action" value=(query.action);
+            input type="text" name="target" placeholder="Target name or ID" value=(query.target);
+            button type="submit" { "Filter" }
+
+            table class="audit-log" {
+                thead {
+                    tr {
+                        th { "Time" }
+                        th { "Admin" }
+                        th { "Action" }
+                        th { "Target" }
+                        th { "Reason" }
+                        th { "Source IP" }
+                    }
+                }
+                tbody {
+                    @for entry in entries {
+                        tr {
+                            td { (format_timestamp(entry.timestamp)) }
+                            td { (entry.admin) }
+                            td { (entry.action) }
+                            td {
+                                @match (&entry.target_name, &entry.target_id) {
+                                    (Some(name), Some(id)) => { (name) " (ID: " (id) ")" },
+                                    (None, Some(id)) => { (id) },
+                                    _ => {},
+                                }
+                            }
+                            td { (entry.reason.unwrap_or_default()) }
+                            td { (entry.source_ip.unwrap_or_default()) }
+                        }
+                    }
+                }
+            }
+
+            // The paging buttons submit the filters along with the page number.
+            div class="paging" {
+                @if page > 0 {
+                    button type="submit" name="page" value=(page - 1) { "Previous" }
+                }
+                span { "Page " (page + 1) " of " (page_count) }
+                @if page + 1 < page_count {
+                    button type="submit" name="page" value=(page + 1) { "Next" }
+                }
+            }
+        }
+    };
+
+    Ok(render_page(Some(&admin), content))
+}
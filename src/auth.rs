@@ -0,0 +1,447 @@
+use std::{
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use axum::{
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+use bevy::{prelude::*, utils::HashMap};
+use bevy_defer::{AsyncAccess, AsyncWorld};
+use maud::html;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::ApiError,
+    assets::STATIC_PATH,
+    console::ConsoleEntry,
+    template::render_page,
+    tokens, RconConfig, RconForbidden, RconPermission, RconRole,
+};
+
+/// The name of the cookie that holds the session token.
+const SESSION_COOKIE: &str = "rcon_session";
+
+/// How many console commands are kept in the history of a session.
+const CONSOLE_HISTORY_SIZE: usize = 100;
+
+/// How many failed logins for a username or address are allowed before further attempts are delayed.
+const FREE_LOGIN_ATTEMPTS: u32 = 3;
+
+/// The longest delay between login attempts for a username or address.
+/// Failures are forgotten after this long without one.
+const MAX_LOGIN_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+/// A hash that login attempts for unknown usernames are checked against, see `login`.
+static DUMMY_PASSWORD_HASH: LazyLock<String> = LazyLock::new(|| hash_password("rcon-unknown-admin"));
+
+/// Paths that can be reached without being logged in, besides the static files.
+const PUBLIC_PATHS: &[&str] = &["/login"];
+
+/// An admin account that can log into the panel.
+/// Accounts can be defined in code with `RconPlugin::admin`, or spawned as components, in which case
+/// they are stored in the database. Use `hash_password` to create the password hash.
+#[derive(Component, Clone, Default, Serialize, Deserialize, PartialEq, Reflect)]
+pub struct DbRconAdmin {
+    pub username: String,
+    pub password_hash: String,
+    pub role: RconRole,
+}
+
+/// The admin that is logged in for the current request.
+/// Inserted into the request extensions by the session middleware, so handlers can extract it with
+/// `axum::Extension<RconAdmin>`.
+#[derive(Clone, Debug)]
+pub struct RconAdmin {
+    pub username: String,
+    pub role: RconRole,
+}
+
+impl RconAdmin {
+    /// Whether the admin has the given permission.
+    pub fn can(&self, permission: RconPermission) -> bool {
+        self.role.allows(permission)
+    }
+
+    /// Returns an error response if the admin does not have the given permission.
+    pub fn require(&self, permission: RconPermission) -> Result<(), RconForbidden> {
+        if self.can(permission) {
+            Ok(())
+        } else {
+            warn!("RCON admin {} tried to {} without permission", self.username, permission);
+            Err(RconForbidden(permission))
+        }
+    }
+}
+
+/// A resource that contains the active login sessions, keyed by session token,
+/// and the recent failed logins, keyed by username.
+/// How a client of a long-lived connection authenticated, kept to check it again while the connection is open.
+#[derive(Clone)]
+pub(crate) enum RconCredential {
+    /// A panel session token.
+    Session(String),
+    /// An API token.
+    Token(String),
+}
+
+impl RconCredential {
+    /// The admin the credential currently belongs to, `None` once the session has ended or the token was revoked.
+    /// Does not record that the token was used, so it can be called for every update sent.
+    pub(crate) fn admin(&self, world: &mut World) -> Option<RconAdmin> {
+        match self {
+            RconCredential::Session(session) => world.resource_mut::<RconSessions>().get(session),
+            RconCredential::Token(token) => tokens::token_admin(world, token),
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub(crate) struct RconSessions {
+    sessions: HashMap<String, RconSession>,
+    /// The failed logins per username.
+    failed_logins: RconLoginThrottle,
+}
+
+struct RconSession {
+    admin: RconAdmin,
+    expires_at: Instant,
+    /// The commands run in the web console during this session, oldest first.
+    console_history: Vec<ConsoleEntry>,
+}
+
+/// Failed logins per username or address, used to delay brute force attempts.
+#[derive(Default)]
+pub(crate) struct RconLoginThrottle {
+    failed: HashMap<String, FailedLogins>,
+}
+
+struct FailedLogins {
+    count: u32,
+    last_failure: Instant,
+}
+
+impl FailedLogins {
+    /// When the next attempt is allowed. The delay doubles with every failure past the free attempts.
+    fn retry_at(&self) -> Instant {
+        let excess = self.count.saturating_sub(FREE_LOGIN_ATTEMPTS);
+        if excess == 0 {
+            return self.last_failure;
+        }
+        let backoff = Duration::from_secs(1 << (excess - 1).min(16)).min(MAX_LOGIN_BACKOFF);
+        self.last_failure + backoff
+    }
+}
+
+impl RconLoginThrottle {
+    /// How long until the next login attempt for the key is allowed, if it is delayed.
+    fn delay(&self, key: &str) -> Option<Duration> {
+        let retry_at = self.failed.get(key)?.retry_at();
+        retry_at.checked_duration_since(Instant::now()).filter(|delay| !delay.is_zero())
+    }
+
+    /// Starts a login attempt for the key, counted as failed until `succeed` clears it.
+    /// The delay is checked and the attempt counted at once, so parallel attempts can not all pass
+    /// the check before the first of them fails. Returns the delay if attempts are delayed.
+    pub(crate) fn start(&mut self, key: &str) -> Result<(), Duration> {
+        if let Some(delay) = self.delay(key) {
+            return Err(delay);
+        }
+        self.record_failure(key);
+        Ok(())
+    }
+
+    /// Forgets the failed logins of the key after a successful login.
+    pub(crate) fn succeed(&mut self, key: &str) {
+        self.failed.remove(key);
+    }
+
+    /// Records a failed login for the key. Failures older than `MAX_LOGIN_BACKOFF` are forgotten,
+    /// so the map does not grow with every key that was tried.
+    fn record_failure(&mut self, key: &str) {
+        let now = Instant::now();
+        self.failed
+            .retain(|_, failed| now.duration_since(failed.last_failure) < MAX_LOGIN_BACKOFF);
+
+        let failed = self.failed.entry(key.to_string()).or_insert(FailedLogins {
+            count: 0,
+            last_failure: now,
+        });
+        failed.count += 1;
+        failed.last_failure = now;
+    }
+}
+
+impl RconSessions {
+    /// Creates a new session for the admin and returns its token. Also drops the sessions that have expired.
+    pub(crate) fn create(&mut self, admin: RconAdmin, ttl: Duration) -> String {
+        let now = Instant::now();
+        self.sessions.retain(|_, session| session.expires_at > now);
+
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let token: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+
+        self.sessions.insert(token.clone(), RconSession {
+            admin,
+            expires_at: now + ttl,
+            console_history: vec![],
+        });
+        token
+    }
+
+    /// Returns the admin for a session token, dropping the session if it has expired.
+    pub(crate) fn get(&mut self, token: &str) -> Option<RconAdmin> {
+        let session = self.sessions.get(token)?;
+        if session.expires_at <= Instant::now() {
+            self.sessions.remove(token);
+            return None;
+        }
+        Some(session.admin.clone())
+    }
+
+    pub(crate) fn remove(&mut self, token: &str) {
+        self.sessions.remove(token);
+    }
+
+    /// The console history of a session, oldest first.
+    pub(crate) fn console_history(&self, token: &str) -> Vec<ConsoleEntry> {
+        self.sessions
+            .get(token)
+            .map(|session| session.console_history.clone())
+            .unwrap_or_default()
+    }
+
+    /// Adds a command to the console history of a session, dropping the oldest entries when it is full.
+    pub(crate) fn push_console_history(&mut self, token: &str, entry: ConsoleEntry) {
+        if let Some(session) = self.sessions.get_mut(token) {
+            session.console_history.push(entry);
+            let overflow = session.console_history.len().saturating_sub(CONSOLE_HISTORY_SIZE);
+            session.console_history.drain(..overflow);
+        }
+    }
+}
+
+/// Hashes a password with Argon2, for use in `RconPlugin::admin` or `DbRconAdmin`.
+pub fn hash_password(password: &str) -> String {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("Failed to hash password")
+        .to_string()
+}
+
+/// Checks a password against a hash created by `hash_password`.
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    let Ok(parsed) = PasswordHash::new(password_hash) else {
+        error!("Invalid password hash stored for an RCON admin");
+        return false;
+    };
+    Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok()
+}
+
+/// Reads the session token from the request cookies.
+pub(crate) fn session_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, value)| value.to_string())
+}
+
+/// Middleware that only lets requests with a valid session through.
+/// Other requests are redirected to the login page, or get a JSON error for the API.
+pub(crate) async fn require_session(mut request: Request, next: Next) -> Response {
+    let path = request.uri().path();
+    if PUBLIC_PATHS.contains(&path) || path.starts_with(STATIC_PATH) {
+        return next.run(request).await;
+    }
+
+    let login = session_token(request.headers()).and_then(|token| {
+        let admin = AsyncWorld
+            .resource::<RconSessions>()
+            .get_mut(|sessions| sessions.get(&token))
+            .ok()
+            .flatten()?;
+        Some((admin, RconCredential::Session(token)))
+    });
+
+    // The JSON API also accepts API tokens, for scripts and bots that can not log in.
+    let login = match (login, tokens::bearer_token(request.headers())) {
+        (None, Some(token)) if request.uri().path().starts_with("/api/") => AsyncWorld.run(move |world: &mut World| {
+            let admin = tokens::authenticate(world, &token)?;
+            Some((admin, RconCredential::Token(token)))
+        }),
+        (login, _) => login,
+    };
+
+    match login {
+        Some((admin, credential)) => {
+            // The credential is kept by long-lived responses like the event stream, to check it again.
+            request.extensions_mut().insert(admin);
+            request.extensions_mut().insert(credential);
+            next.run(request).await
+        }
+        None if request.uri().path().starts_with("/api/") => {
+            ApiError::new(StatusCode::UNAUTHORIZED, "Not logged in, or invalid API token").into_response()
+        }
+        None if request.headers().contains_key("HX-Request") => {
+            // htmx swaps the response into the page, so ask it to navigate instead.
+            (StatusCode::UNAUTHORIZED, [("HX-Redirect", "/login")]).into_response()
+        }
+        None => Redirect::to("/login").into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct LoginForm {
+    username: String,
+    password: String,
+}
+
+/// The login page.
+pub(crate) async fn login_page() -> axum::response::Html<String> {
+    login_markup(None)
+}
+
+/// Checks the submitted credentials against the configured and stored admin accounts,
+/// and starts a session if they match. Repeated failures for a username delay further attempts.
+pub(crate) async fn login(form: axum::extract::Form<LoginForm>) -> Response {
+    let LoginForm { username, password } = form.0;
+
+    let started = AsyncWorld
+        .resource::<RconSessions>()
+        .get_mut(|sessions| sessions.failed_logins.start(&username))
+        .unwrap_or(Ok(()));
+    if let Err(delay) = started {
+        warn!("Delayed RCON login attempt for {} after repeated failures", username);
+        let message = format!("Too many failed attempts, try again in {} seconds", delay.as_secs().max(1));
+        return (StatusCode::TOO_MANY_REQUESTS, login_markup(Some(&message))).into_response();
+    }
+
+    let account = AsyncWorld.run({
+        let username = username.clone();
+        move |world: &mut World| find_admin(world, &username)
+    });
+
+    // Unknown usernames are checked against a dummy hash, so they take as long as known ones.
+    let (password_hash, role) = match account {
+        Some(account) => (Some(account.password_hash), Some(account.role)),
+        None => (None, None),
+    };
+    let verified = AsyncWorld
+        .unblock(move || verify_password(&password, password_hash.as_deref().unwrap_or(&DUMMY_PASSWORD_HASH)))
+        .await;
+
+    let Some(role) = role.filter(|_| verified) else {
+        // The attempt was already counted as failed when it started.
+        warn!("Failed RCON login attempt for {}", username);
+        return (StatusCode::UNAUTHORIZED, login_markup(Some("Invalid username or password"))).into_response();
+    };
+
+    let token = AsyncWorld.run(move |world: &mut World| {
+        let ttl = world.resource::<RconConfig>().session_ttl;
+        info!("RCON admin {} logged in as {}", username, role);
+        let mut sessions = world.resource_mut::<RconSessions>();
+        sessions.failed_logins.succeed(&username);
+        sessions.create(RconAdmin { username, role }, ttl)
+    });
+
+    let cookie = format!("{}={}; Path=/; HttpOnly; SameSite=Strict", SESSION_COOKIE, token);
+    session_response(cookie)
+}
+
+/// Ends the current session.
+pub(crate) async fn logout(headers: HeaderMap) -> Response {
+    if let Some(token) = session_token(&headers) {
+        if let Err(e) = AsyncWorld.resource::<RconSessions>().get_mut(|sessions| sessions.remove(&token)) {
+            error!("Failed to remove session: {}", e);
+        }
+    }
+
+    let cookie = format!("{}=; Path=/; HttpOnly; SameSite=Strict; Max-Age=0", SESSION_COOKIE);
+    session_response(cookie)
+}
+
+/// Redirects to the index page while setting the session cookie.
+/// The index page redirects to the login page when the session is gone.
+fn session_response(cookie: String) -> Response {
+    let mut response = Redirect::to("/").into_response();
+    match HeaderValue::from_str(&cookie) {
+        Ok(cookie) => {
+            response.headers_mut().insert(header::SET_COOKIE, cookie);
+        }
+        Err(e) => error!("Invalid session cookie: {}", e),
+    }
+    response
+}
+
+/// Finds an admin account by username, looking at the configured accounts first and then the database.
+fn find_admin(world: &mut World, username: &str) -> Option<DbRconAdmin> {
+    if let Some(account) = world
+        .resource::<RconConfig>()
+        .admins
+        .iter()
+        .find(|account| account.username == username)
+    {
+        return Some(account.clone());
+    }
+
+    let mut admins = world.query::<&DbRconAdmin>();
+    admins
+        .iter(world)
+        .find(|account| account.username == username)
+        .cloned()
+}
+
+fn login_markup(error: Option<&str>) -> axum::response::Html<String> {
+    let content = html! {
+        h3 { "Login" }
+        @if let Some(error) = error {
+            p class="error" { (error) }
+        }
+        form method="post" action="/login" {
+            input type="text" name="username" placeholder="Username" required;
+            input type="password" name="password" placeholder="Password" required;
+            button type="submit" { "Login" }
+        }
+    };
+
+    render_page(None, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays_attempts_after_the_free_ones() {
+        let mut throttle = RconLoginThrottle::default();
+        for _ in 0..=FREE_LOGIN_ATTEMPTS {
+            assert!(throttle.start("203.0.113.7").is_ok());
+        }
+        assert!(throttle.start("203.0.113.7").is_err());
+        assert!(throttle.start("198.51.100.23").is_ok());
+    }
+
+    #[test]
+    fn forgets_failures_after_a_success() {
+        let mut throttle = RconLoginThrottle::default();
+        for _ in 0..=FREE_LOGIN_ATTEMPTS {
+            assert!(throttle.start("admin").is_ok());
+        }
+        throttle.succeed("admin");
+        assert!(throttle.start("admin").is_ok());
+    }
+}
@@ -0,0 +1,331 @@
+use std::{net::IpAddr, time::Duration};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions, audit::unix_now, entities, DbRconBannedPlayer, DbRconIpBan, RconActor, RconConfig, RconIpRange,
+    RconPlayer, RconPlayerComponent, RconPlayerRemoved, RconPlayers,
+};
+
+/// When a temporary ban ends, stored next to the `DbRconBannedPlayer` on the ban entity.
+/// Bans without this component are permanent. Kept as a separate component
+/// so that databases created before temporary bans existed still load.
+#[derive(Component, Clone, Default, Serialize, Deserialize, PartialEq, Reflect)]
+pub struct DbRconBanExpiry {
+    /// Seconds since the unix epoch.
+    pub expires_at: u64,
+}
+
+impl DbRconBanExpiry {
+    /// The ban expiry for a ban of the given length, starting now.
+    pub fn from_now(duration: Duration) -> Self {
+        Self {
+            expires_at: unix_now().saturating_add(duration.as_secs()),
+        }
+    }
+
+    /// How long until the ban ends, zero if it already has.
+    pub fn remaining(&self) -> Duration {
+        Duration::from_secs(self.expires_at.saturating_sub(unix_now()))
+    }
+}
+
+/// Context stored next to the `DbRconBannedPlayer` on the ban entity, for handling ban appeals.
+/// Kept as a separate component so that databases created before ban details existed still load,
+/// bans from those databases simply have no details.
+#[derive(Component, Clone, Default, Serialize, Deserialize, PartialEq, Reflect)]
+pub struct DbRconBanDetails {
+    pub reason: Option<String>,
+    /// The username of the admin that issued the ban.
+    pub issued_by: String,
+    /// Seconds since the unix epoch.
+    pub created_at: u64,
+    /// Free-form notes, e.g. links to evidence.
+    pub notes: Option<String>,
+}
+
+/// The options for a new ban.
+#[derive(Clone, Default, Debug)]
+pub struct RconBanOptions {
+    /// How long the ban lasts, `None` for a permanent ban.
+    pub duration: Option<Duration>,
+    pub reason: Option<String>,
+    pub notes: Option<String>,
+}
+
+/// Everything stored about a ban.
+#[derive(Clone, Serialize)]
+pub struct RconBanInfo {
+    pub banned: DbRconBannedPlayer,
+    /// `None` for a permanent ban.
+    pub expiry: Option<DbRconBanExpiry>,
+    /// `None` for bans created before ban details were stored.
+    pub details: Option<DbRconBanDetails>,
+}
+
+/// A `SystemParam` for game code to check and manage bans, e.g. when a player connects.
+/// Check both `is_banned` with the player's unique ID and `is_ip_banned` with their address.
+/// Bans and unbans go through the same path as the web panel, so they are written to the audit log
+/// and send the `RconPlayerBanned`, `RconPlayerUnbanned`, `RconIpBanned` and `RconIpUnbanned` events.
+/// They are applied with the system's commands.
+#[derive(SystemParam)]
+pub struct RconBans<'w, 's> {
+    bans: Query<
+        'w,
+        's,
+        (
+            &'static DbRconBannedPlayer,
+            Option<&'static DbRconBanExpiry>,
+            Option<&'static DbRconBanDetails>,
+        ),
+    >,
+    ip_bans: Query<'w, 's, &'static DbRconIpBan>,
+    commands: Commands<'w, 's>,
+}
+
+impl RconBans<'_, '_> {
+    /// Whether the player with the given unique ID is banned. Expired bans do not count.
+    pub fn is_banned(&self, unique_id: &str) -> bool {
+        self.ban_info(unique_id).is_some()
+    }
+
+    /// Returns the ban for the player with the given unique ID, if they are banned. Expired bans are ignored.
+    pub fn ban_info(&self, unique_id: &str) -> Option<RconBanInfo> {
+        let now = unix_now();
+        self.bans
+            .iter()
+            .filter(|(banned, _, _)| banned.unique_id == unique_id)
+            .find(|(_, expiry, _)| expiry.is_none_or(|expiry| expiry.expires_at > now))
+            .map(|(banned, expiry, details)| RconBanInfo {
+                banned: banned.clone(),
+                expiry: expiry.cloned(),
+                details: details.cloned(),
+            })
+    }
+
+    /// Bans a player. Failures, like the player already being banned, are logged.
+    pub fn ban(&mut self, player: RconPlayer, options: RconBanOptions) {
+        self.commands.queue(move |world: &mut World| {
+            if let Err(e) = actions::ban_player(world, &RconActor::game(), player, options) {
+                warn!("Failed to ban player: {}", e);
+            }
+        });
+    }
+
+    /// Unbans the player with the given unique ID. Failures, like the player not being banned, are logged.
+    pub fn unban(&mut self, unique_id: impl Into<String>) {
+        let unique_id = unique_id.into();
+        self.commands.queue(move |world: &mut World| {
+            if let Err(e) = actions::unban_player(world, &RconActor::game(), &unique_id) {
+                warn!("Failed to unban player: {}", e);
+            }
+        });
+    }
+
+    /// Whether the IP address is in a banned range. Expired bans do not count.
+    pub fn is_ip_banned(&self, ip: IpAddr) -> bool {
+        self.ip_ban_info(ip).is_some()
+    }
+
+    /// Returns the ban of a range that contains the IP address, if any. Expired bans are ignored.
+    pub fn ip_ban_info(&self, ip: IpAddr) -> Option<DbRconIpBan> {
+        self.ip_bans
+            .iter()
+            .find(|ban| ban.range.contains(ip) && ban.is_active())
+            .cloned()
+    }
+
+    /// Bans an IP address or range, e.g. `"203.0.113.0/24".parse()?`. Failures, like the range already
+    /// being banned, are logged.
+    pub fn ban_ip(&mut self, range: impl Into<RconIpRange>, options: RconBanOptions) {
+        let range = range.into();
+        self.commands.queue(move |world: &mut World| {
+            if let Err(e) = actions::ban_ip(world, &RconActor::game(), range, options) {
+                warn!("Failed to ban IP: {}", e);
+            }
+        });
+    }
+
+    /// Lifts the ban of exactly the given IP address or range. Failures, like it not being banned, are logged.
+    pub fn unban_ip(&mut self, range: impl Into<RconIpRange>) {
+        let range = range.into();
+        self.commands.queue(move |world: &mut World| {
+            if let Err(e) = actions::unban_ip(world, &RconActor::game(), range) {
+                warn!("Failed to unban IP: {}", e);
+            }
+        });
+    }
+
+    /// Whether a connecting player is banned by their unique ID or, if known, their IP address.
+    fn refuses(&self, player: &RconPlayer) -> bool {
+        self.is_banned(&player.unique_id) || player.ip.is_some_and(|ip| self.is_ip_banned(ip))
+    }
+}
+
+/// An event that is sent to the plugin user when a banned player was added to `RconPlayers`
+/// and removed again by the join hook, see `RconPlugin::refuse_banned_players`.
+/// The plugin user can then perform the appropriate action to disconnect the player.
+/// At least one of `ban` and `ip_ban` is set.
+#[derive(Event)]
+pub struct RconPlayerRefused {
+    pub player: RconPlayer,
+    /// The ban of the player's unique ID, if any.
+    pub ban: Option<RconBanInfo>,
+    /// The ban of a range containing the player's IP address, if any.
+    pub ip_ban: Option<DbRconIpBan>,
+    /// The player's entity, if player entities are enabled.
+    pub entity: Option<Entity>,
+}
+
+/// The join hook, removes banned players from `RconPlayers` as soon as they are added.
+/// Their entities are despawned or tagged if player entities are enabled.
+pub(crate) fn refuse_banned_players(
+    mut players: ResMut<RconPlayers>,
+    bans: RconBans,
+    mut refused: EventWriter<RconPlayerRefused>,
+    entities: Query<(Entity, &RconPlayerComponent), Without<RconPlayerRemoved>>,
+    config: Res<RconConfig>,
+    mut commands: Commands,
+) {
+    // Only take a mutable borrow when there is something to remove, to not trigger change detection every frame.
+    if !players.players.iter().any(|player| bans.refuses(player)) {
+        return;
+    }
+
+    players.players.retain(|player| {
+        if !bans.refuses(player) {
+            return true;
+        }
+
+        info!("Refused banned player {}", player);
+        let entity = config.player_entities.and_then(|mode| {
+            let (entity, _) = entities.iter().find(|(_, entity)| entity.unique_id == player.unique_id)?;
+            entities::remove_player_entity(&mut commands, mode, entity, RconPlayerRemoved::Refused);
+            Some(entity)
+        });
+        refused.send(RconPlayerRefused {
+            player: player.clone(),
+            ban: bans.ban_info(&player.unique_id),
+            ip_ban: player.ip.and_then(|ip| bans.ip_ban_info(ip)),
+            entity,
+        });
+        false
+    });
+}
+
+/// Lifts temporary bans that have expired, which also sends a `RconPlayerUnbanned` or `RconIpUnbanned` event.
+pub(crate) fn lift_expired_bans(world: &mut World) {
+    let now = unix_now();
+    let mut bans = world.query::<(&DbRconBannedPlayer, &DbRconBanExpiry)>();
+    let expired: Vec<String> = bans
+        .iter(world)
+        .filter(|(_, expiry)| expiry.expires_at <= now)
+        .map(|(banned, _)| banned.unique_id.clone())
+        .collect();
+
+    let actor = RconActor::system();
+    for unique_id in expired {
+        if let Err(e) = actions::unban_player(world, &actor, &unique_id) {
+            error!("Failed to lift expired ban: {}", e);
+        }
+    }
+
+    let mut ip_bans = world.query::<&DbRconIpBan>();
+    let expired: Vec<RconIpRange> = ip_bans
+        .iter(world)
+        .filter(|ban| !ban.is_active())
+        .map(|ban| ban.range)
+        .collect();
+
+    for range in expired {
+        if let Err(e) = actions::unban_ip(world, &actor, range) {
+            error!("Failed to lift expired IP ban: {}", e);
+        }
+    }
+}
+
+/// Parses a duration like `90s`, `30m`, `12h`, `7d` or `2w`. A bare number is read as minutes.
+pub fn parse_duration(input: &str) -> Option<Duration> {
+    let input = input.trim();
+    let split = input.find(|c: char| !c.is_ascii_digit()).unwrap_or(input.len());
+    let (amount, unit) = input.split_at(split);
+    let amount: u64 = amount.parse().ok()?;
+
+    let seconds = match unit.trim() {
+        "s" => 1,
+        "" | "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+
+    amount.checked_mul(seconds).map(Duration::from_secs)
+}
+
+/// Formats a duration as its two largest units, e.g. `2d 4h` or `15m 30s`.
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let parts = [
+        (seconds / 86400, "d"),
+        (seconds / 3600 % 24, "h"),
+        (seconds / 60 % 60, "m"),
+        (seconds % 60, "s"),
+    ];
+
+    let formatted: Vec<String> = parts
+        .iter()
+        .skip_while(|(amount, _)| *amount == 0)
+        .take(2)
+        .filter(|(amount, _)| *amount > 0)
+        .map(|(amount, unit)| format!("{}{}", amount, unit))
+        .collect();
+
+    if formatted.is_empty() {
+        "0s".to_string()
+    } else {
+        formatted.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations_with_units() {
+        assert_eq!(parse_duration("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30m"), Some(Duration::from_secs(30 * 60)));
+        assert_eq!(parse_duration("12h"), Some(Duration::from_secs(12 * 60 * 60)));
+        assert_eq!(parse_duration("7d"), Some(Duration::from_secs(7 * 24 * 60 * 60)));
+        assert_eq!(parse_duration("2w"), Some(Duration::from_secs(14 * 24 * 60 * 60)));
+    }
+
+    #[test]
+    fn parses_bare_numbers_as_minutes() {
+        assert_eq!(parse_duration("15"), Some(Duration::from_secs(15 * 60)));
+        assert_eq!(parse_duration(" 5 m "), Some(Duration::from_secs(5 * 60)));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("m"), None);
+        assert_eq!(parse_duration("7x"), None);
+        assert_eq!(parse_duration("-1d"), None);
+        assert_eq!(parse_duration("1.5h"), None);
+        assert_eq!(parse_duration("99999999999999999999w"), None);
+        assert_eq!(parse_duration(&format!("{}w", u64::MAX)), None);
+    }
+
+    #[test]
+    fn formats_the_two_largest_units() {
+        assert_eq!(format_duration(Duration::ZERO), "0s");
+        assert_eq!(format_duration(Duration::from_secs(45)), "45s");
+        assert_eq!(format_duration(Duration::from_secs(15 * 60 + 30)), "15m 30s");
+        assert_eq!(format_duration(Duration::from_secs(2 * 86400 + 4 * 3600 + 59)), "2d 4h");
+        assert_eq!(format_duration(Duration::from_secs(3600 + 5)), "1h");
+        assert_eq!(format_duration(Duration::from_secs(86400 + 60)), "1d");
+    }
+}
@@ -0,0 +1,734 @@
+use std::{collections::BTreeMap, future::Future, sync::Arc, time::Duration};
+
+use bevy::prelude::*;
+use maud::Markup;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    actions, audit, pages, parse_duration, player_actions, RconActor, RconBanOptions, RconIpRange, RconPage,
+    RconPageRequest, RconPermission, RconPlayerAction, RconPlayers, RconRole,
+};
+
+/// Who is running a command. Handlers can take it as an argument to find out.
+#[derive(Clone, Debug)]
+pub struct RconCaller {
+    pub actor: RconActor,
+    /// Decides which commands the caller may run.
+    pub role: RconRole,
+}
+
+/// The description of a console command, built from its name with `RconCommand::new` or from a `&str`.
+#[derive(Clone)]
+pub struct RconCommand {
+    pub name: String,
+    pub help: String,
+    /// The arguments, e.g. `<unique_id> [reason]`.
+    pub usage: String,
+    /// The permission needed to run the command. Defaults to `RconPermission::RunCommands`.
+    pub permission: RconPermission,
+    /// Whether running the command is written to the audit log. Built-in commands
+    /// that go through the action layer are audited there instead.
+    audited: bool,
+}
+
+impl RconCommand {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            help: String::new(),
+            usage: String::new(),
+            permission: RconPermission::RunCommands,
+            audited: true,
+        }
+    }
+
+    pub fn help(mut self, help: impl Into<String>) -> Self {
+        self.help = help.into();
+        self
+    }
+
+    pub fn usage(mut self, usage: impl Into<String>) -> Self {
+        self.usage = usage.into();
+        self
+    }
+
+    pub fn permission(mut self, permission: RconPermission) -> Self {
+        self.permission = permission;
+        self
+    }
+
+    fn unaudited(mut self) -> Self {
+        self.audited = false;
+        self
+    }
+}
+
+impl From<&str> for RconCommand {
+    fn from(name: &str) -> Self {
+        RconCommand::new(name)
+    }
+}
+
+impl From<String> for RconCommand {
+    fn from(name: String) -> Self {
+        RconCommand::new(name)
+    }
+}
+
+type BoxedHandler = Arc<dyn Fn(&mut World, &mut RconArgs) -> Result<String, String> + Send + Sync>;
+
+struct RegisteredCommand {
+    command: RconCommand,
+    handler: BoxedHandler,
+}
+
+/// A resource that contains the registered console commands.
+/// The web console, the JSON API and the Source RCON listener run commands from here.
+#[derive(Resource, Default)]
+pub struct RconCommands {
+    commands: BTreeMap<String, RegisteredCommand>,
+}
+
+impl RconCommands {
+    /// The registered commands, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = &RconCommand> {
+        self.commands.values().map(|registered| &registered.command)
+    }
+
+    /// Parses and runs a command line, checking that the caller may run the command.
+    /// Returns the output of the command.
+    pub fn execute(world: &mut World, caller: &RconCaller, line: &str) -> Result<String, RconCommandError> {
+        let mut tokens = tokenize(line);
+        if tokens.is_empty() {
+            return Ok(String::new());
+        }
+        let name = tokens.remove(0);
+
+        let Some((command, handler)) = world
+            .get_resource::<RconCommands>()
+            .and_then(|commands| commands.commands.get(&name))
+            .map(|registered| (registered.command.clone(), registered.handler.clone()))
+        else {
+            return Err(RconCommandError::UnknownCommand(name));
+        };
+
+        if !caller.role.allows(command.permission) {
+            return Err(RconCommandError::Forbidden(command.permission));
+        }
+
+        if command.audited {
+            audit::record(world, &caller.actor, "command", None, Some(line.trim().to_string()));
+        }
+
+        let mut args = RconArgs { caller, tokens, position: 0 };
+        handler(world, &mut args).map_err(|e| match command.usage.is_empty() {
+            true => RconCommandError::Failed(e),
+            false => RconCommandError::Failed(format!("{}\nUsage: {} {}", e, command.name, command.usage)),
+        })
+    }
+}
+
+/// The reasons running a command can fail.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RconCommandError {
+    /// No command with the given name is registered.
+    UnknownCommand(String),
+    /// The caller lacks the permission the command requires.
+    Forbidden(RconPermission),
+    /// The arguments could not be parsed, or the handler returned an error.
+    Failed(String),
+}
+
+impl std::fmt::Display for RconCommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RconCommandError::UnknownCommand(name) => {
+                write!(f, "Unknown command: {}. Type help for a list of commands.", name)
+            }
+            RconCommandError::Forbidden(permission) => write!(f, "You do not have permission to {}.", permission),
+            RconCommandError::Failed(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for RconCommandError {}
+
+/// Splits a command line on whitespace, keeping "quoted arguments" together.
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut token = String::new();
+    let mut quoted = false;
+    let mut in_token = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_token = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut token));
+                    in_token = false;
+                }
+            }
+            c => {
+                token.push(c);
+                in_token = true;
+            }
+        }
+    }
+    if in_token {
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+/// The arguments of a command that is being run.
+pub struct RconArgs<'a> {
+    caller: &'a RconCaller,
+    tokens: Vec<String>,
+    position: usize,
+}
+
+impl RconArgs<'_> {
+    /// The next argument, if there is one left.
+    pub fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.position).map(String::as_str)
+    }
+
+    /// Takes the next argument, or returns an error if there is none left.
+    pub fn next_token(&mut self) -> Result<String, String> {
+        let token = self.tokens.get(self.position).cloned().ok_or("Missing argument")?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    /// Takes all arguments that are left, joined with spaces.
+    pub fn rest(&mut self) -> String {
+        let rest = self.tokens[self.position..].join(" ");
+        self.position = self.tokens.len();
+        rest
+    }
+
+    pub fn caller(&self) -> &RconCaller {
+        self.caller
+    }
+
+    fn finish(&self) -> Result<(), String> {
+        match self.tokens.len() - self.position {
+            0 => Ok(()),
+            _ => Err("Too many arguments".to_string()),
+        }
+    }
+}
+
+/// A type that can be parsed from the arguments of a command.
+pub trait RconArg: Sized {
+    fn parse(args: &mut RconArgs) -> Result<Self, String>;
+
+    /// Whether the token is meant as this kind of argument. An optional argument leaves a token that is not
+    /// for the next argument, and reports an error for a token that is but does not parse, e.g. a duration of `7x`.
+    fn looks_like(_token: &str) -> bool {
+        true
+    }
+}
+
+/// Implements `RconArg` with `FromStr`, for the types that `looks_like` accepts as arguments.
+macro_rules! impl_rcon_arg_from_str {
+    ($looks_like:path => $($ty:ty),*) => {
+        $(
+            impl RconArg for $ty {
+                fn parse(args: &mut RconArgs) -> Result<Self, String> {
+                    let token = args.next_token()?;
+                    token
+                        .parse()
+                        .map_err(|_| format!("Invalid argument {}, expected {}", token, stringify!($ty)))
+                }
+
+                fn looks_like(token: &str) -> bool {
+                    $looks_like(token)
+                }
+            }
+        )*
+    };
+}
+
+impl_rcon_arg_from_str!(looks_like_anything => String, bool);
+impl_rcon_arg_from_str!(looks_like_number => i8, i16, i32, i64, u8, u16, u32, u64, usize, f32, f64);
+
+fn looks_like_anything(_token: &str) -> bool {
+    true
+}
+
+fn looks_like_number(token: &str) -> bool {
+    token.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '-' | '+' | '.'))
+}
+
+/// A duration like `30m` or `7d`, see `parse_duration`.
+impl RconArg for Duration {
+    fn parse(args: &mut RconArgs) -> Result<Self, String> {
+        let token = args.next_token()?;
+        parse_duration(&token).ok_or_else(|| format!("Invalid duration {}, expected e.g. 30m, 12h or 7d", token))
+    }
+
+    fn looks_like(token: &str) -> bool {
+        token.starts_with(|c: char| c.is_ascii_digit())
+    }
+}
+
+/// An IP address or CIDR range like `203.0.113.0/24`.
+impl RconArg for RconIpRange {
+    fn parse(args: &mut RconArgs) -> Result<Self, String> {
+        let token = args.next_token()?;
+        token.parse().map_err(|e: crate::RconInvalidIpRange| e.to_string())
+    }
+
+    fn looks_like(token: &str) -> bool {
+        token.contains(['.', ':'])
+    }
+}
+
+/// An optional argument. It is absent when no arguments are left, or when the next one does not look like it,
+/// in which case that one is left for the next argument. Otherwise it has to parse.
+impl<T: RconArg> RconArg for Option<T> {
+    fn parse(args: &mut RconArgs) -> Result<Self, String> {
+        match args.peek() {
+            Some(token) if T::looks_like(token) => T::parse(args).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+impl RconArg for RconCaller {
+    fn parse(args: &mut RconArgs) -> Result<Self, String> {
+        Ok(args.caller().clone())
+    }
+}
+
+/// All arguments that are left, joined with spaces. Fails if there are none, use `Option<RconRest>` to allow that.
+#[derive(Clone, Debug, Deref)]
+pub struct RconRest(pub String);
+
+impl RconArg for RconRest {
+    fn parse(args: &mut RconArgs) -> Result<Self, String> {
+        match args.rest() {
+            rest if rest.is_empty() => Err("Missing argument".to_string()),
+            rest => Ok(RconRest(rest)),
+        }
+    }
+}
+
+/// The return value of a command handler.
+pub trait IntoRconOutput {
+    fn into_output(self) -> Result<String, String>;
+}
+
+impl IntoRconOutput for String {
+    fn into_output(self) -> Result<String, String> {
+        Ok(self)
+    }
+}
+
+impl IntoRconOutput for &'static str {
+    fn into_output(self) -> Result<String, String> {
+        Ok(self.to_string())
+    }
+}
+
+impl IntoRconOutput for () {
+    fn into_output(self) -> Result<String, String> {
+        Ok(String::new())
+    }
+}
+
+impl<T: IntoRconOutput, E: std::fmt::Display> IntoRconOutput for Result<T, E> {
+    fn into_output(self) -> Result<String, String> {
+        self.map_err(|e| e.to_string()).and_then(IntoRconOutput::into_output)
+    }
+}
+
+/// A function that can handle a command: it takes `&mut World` followed by up to six `RconArg`s,
+/// and returns an `IntoRconOutput`.
+pub trait RconCommandHandler<Marker>: Send + Sync + 'static {
+    fn run(&self, world: &mut World, args: &mut RconArgs) -> Result<String, String>;
+}
+
+macro_rules! impl_rcon_command_handler {
+    ($($arg:ident),*) => {
+        impl<Func, Out, $($arg),*> RconCommandHandler<fn($($arg),*) -> Out> for Func
+        where
+            Func: Fn(&mut World, $($arg),*) -> Out + Send + Sync + 'static,
+            Out: IntoRconOutput,
+            $($arg: RconArg),*
+        {
+            #[allow(non_snake_case, unused_variables)]
+            fn run(&self, world: &mut World, args: &mut RconArgs) -> Result<String, String> {
+                $(let $arg = $arg::parse(args)?;)*
+                args.finish()?;
+                self(world, $($arg),*).into_output()
+            }
+        }
+    };
+}
+
+impl_rcon_command_handler!();
+impl_rcon_command_handler!(A);
+impl_rcon_command_handler!(A, B);
+impl_rcon_command_handler!(A, B, C);
+impl_rcon_command_handler!(A, B, C, D);
+impl_rcon_command_handler!(A, B, C, D, E);
+impl_rcon_command_handler!(A, B, C, D, E, F);
+
+/// Adds console commands, pages and player actions to the app.
+pub trait RconAppExt {
+    /// Registers a console command, replacing any command with the same name, including the built in ones
+    /// like `kick` and `ban`.
+    /// The command can be a name, or a `RconCommand` with help text and a permission.
+    ///
+    /// ```ignore
+    /// app.add_rcon_command(
+    ///     RconCommand::new("set_gravity").help("Sets the gravity").usage("<value>"),
+    ///     |world: &mut World, value: f32| {
+    ///         world.resource_mut::<Gravity>().0 = value;
+    ///         format!("Gravity set to {}", value)
+    ///     },
+    /// );
+    /// ```
+    fn add_rcon_command<Marker>(
+        &mut self,
+        command: impl Into<RconCommand>,
+        handler: impl RconCommandHandler<Marker>,
+    ) -> &mut Self;
+
+    /// Adds a page to the panel at `path`, listed in the navigation for admins with its permission.
+    /// The page can be a title, or a `RconPage` with a permission.
+    /// The handler returns the content, which is rendered inside the panel layout. It runs on the
+    /// `AsyncWorld` executor, so it can read and change the world. Forms on the page can post to the same path.
+    ///
+    /// ```ignore
+    /// app.add_rcon_page("/match", "Match", |_request: RconPageRequest| async {
+    ///     let score = AsyncWorld.resource::<Score>().get(|score| score.0).unwrap_or_default();
+    ///     html! { h3 { "Match" } p { "Score: " (score) } }
+    /// });
+    /// ```
+    fn add_rcon_page<Fut>(
+        &mut self,
+        path: &str,
+        page: impl Into<RconPage>,
+        handler: impl Fn(RconPageRequest) -> Fut + Send + Sync + 'static,
+    ) -> &mut Self
+    where
+        Fut: Future<Output = Markup> + Send + 'static;
+
+    /// Adds a button to every player in the player list, for admins with the action's permission.
+    /// Pressing it sends a `RconPlayerActionEvent<T>`, with `T` deserialized from the action's form fields,
+    /// and writes the action to the audit log. Use `()` for actions without fields.
+    ///
+    /// ```ignore
+    /// #[derive(Deserialize)]
+    /// struct Mute { minutes: u32 }
+    ///
+    /// app.add_rcon_player_action::<Mute>(RconPlayerAction::new("mute").label("Mute").number_field("minutes", "Minutes"));
+    /// app.add_systems(Update, |mut events: EventReader<RconPlayerActionEvent<Mute>>| {
+    ///     for event in events.read() {
+    ///         info!("Muting {} for {} minutes", event.player, event.data.minutes);
+    ///     }
+    /// });
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if an action with the same name was already added.
+    fn add_rcon_player_action<T>(&mut self, action: impl Into<RconPlayerAction>) -> &mut Self
+    where
+        T: DeserializeOwned + Send + Sync + 'static;
+}
+
+impl RconAppExt for App {
+    fn add_rcon_command<Marker>(
+        &mut self,
+        command: impl Into<RconCommand>,
+        handler: impl RconCommandHandler<Marker>,
+    ) -> &mut Self {
+        let command = command.into();
+        let handler: BoxedHandler = Arc::new(move |world, args| handler.run(world, args));
+
+        self.world_mut()
+            .get_resource_or_init::<RconCommands>()
+            .commands
+            .insert(command.name.clone(), RegisteredCommand { command, handler });
+        self
+    }
+
+    fn add_rcon_page<Fut>(
+        &mut self,
+        path: &str,
+        page: impl Into<RconPage>,
+        handler: impl Fn(RconPageRequest) -> Fut + Send + Sync + 'static,
+    ) -> &mut Self
+    where
+        Fut: Future<Output = Markup> + Send + 'static,
+    {
+        pages::add_page(self, path, page.into(), handler);
+        self
+    }
+
+    fn add_rcon_player_action<T>(&mut self, action: impl Into<RconPlayerAction>) -> &mut Self
+    where
+        T: DeserializeOwned + Send + Sync + 'static,
+    {
+        player_actions::add_player_action::<T>(self, action.into());
+        self
+    }
+}
+
+/// Registers the commands that mirror the web panel.
+/// Commands the game added before the plugin are kept over built in commands with the same name.
+pub(crate) fn add_builtin_commands(app: &mut App) {
+    let game_commands = std::mem::take(&mut app.world_mut().get_resource_or_init::<RconCommands>().commands);
+    register_builtin_commands(app);
+
+    let mut commands = app.world_mut().resource_mut::<RconCommands>();
+    for (name, command) in game_commands {
+        if commands.commands.contains_key(&name) {
+            warn!("The RCON command {} replaces the built in command with the same name", name);
+        }
+        commands.commands.insert(name, command);
+    }
+}
+
+fn register_builtin_commands(app: &mut App) {
+    app.add_rcon_command(
+        RconCommand::new("help")
+            .help("Lists the commands you can run")
+            .permission(RconPermission::ViewPlayers)
+            .unaudited(),
+        |world: &mut World, caller: RconCaller| {
+            let commands = world.resource::<RconCommands>();
+            commands
+                .iter()
+                .filter(|command| caller.role.allows(command.permission))
+                .map(|command| match command.usage.is_empty() {
+                    true => format!("{} - {}", command.name, command.help),
+                    false => format!("{} {} - {}", command.name, command.usage, command.help),
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        },
+    )
+    .add_rcon_command(
+        RconCommand::new("players")
+            .help("Lists the connected players")
+            .permission(RconPermission::ViewPlayers)
+            .unaudited(),
+        |world: &mut World| {
+            let players = world.resource::<RconPlayers>();
+            let mut output = format!("{} players connected", players.players.len());
+            for player in &players.players {
+                output.push_str(&format!("\n{}", player));
+            }
+            output
+        },
+    )
+    .add_rcon_command(
+        RconCommand::new("kick")
+            .help("Kicks a player")
+            .usage("<unique_id> [reason]")
+            .permission(RconPermission::Kick)
+            .unaudited(),
+        |world: &mut World, caller: RconCaller, unique_id: String, reason: Option<RconRest>| {
+            actions::kick_player(world, &caller.actor, &unique_id, reason.map(|reason| reason.0))
+                .map(|player| format!("Kicked {}", player))
+        },
+    )
+    .add_rcon_command(
+        RconCommand::new("ban")
+            .help("Bans a player, e.g. ban steam_123 7d cheating")
+            .usage("<unique_id> [duration] [reason]")
+            .permission(RconPermission::Ban)
+            .unaudited(),
+        |world: &mut World,
+         caller: RconCaller,
+         unique_id: String,
+         duration: Option<Duration>,
+         reason: Option<RconRest>| {
+            let player = actions::player_by_id(world, &unique_id);
+            let options = RconBanOptions {
+                duration,
+                reason: reason.map(|reason| reason.0),
+                notes: None,
+            };
+
+            actions::ban_player(world, &caller.actor, player, options)
+                .map(|player| format!("Banned {}", player))
+        },
+    )
+    .add_rcon_command(
+        RconCommand::new("unban")
+            .help("Unbans a player")
+            .usage("<unique_id>")
+            .permission(RconPermission::Unban)
+            .unaudited(),
+        |world: &mut World, caller: RconCaller, unique_id: String| {
+            actions::unban_player(world, &caller.actor, &unique_id)
+                .map(|player| format!("Unbanned {}", player))
+        },
+    )
+    .add_rcon_command(
+        RconCommand::new("banip")
+            .help("Bans an IP address or range, e.g. banip 203.0.113.0/24 7d ban evasion")
+            .usage("<ip or range> [duration] [reason]")
+            .permission(RconPermission::Ban)
+            .unaudited(),
+        |world: &mut World,
+         caller: RconCaller,
+         range: RconIpRange,
+         duration: Option<Duration>,
+         reason: Option<RconRest>| {
+            let options = RconBanOptions {
+                duration,
+                reason: reason.map(|reason| reason.0),
+                notes: None,
+            };
+
+            actions::ban_ip(world, &caller.actor, range, options).map(|ban| format!("Banned {}", ban.range))
+        },
+    )
+    .add_rcon_command(
+        RconCommand::new("unbanip")
+            .help("Lifts the ban of an IP address or range")
+            .usage("<ip or range>")
+            .permission(RconPermission::Unban)
+            .unaudited(),
+        |world: &mut World, caller: RconCaller, range: RconIpRange| {
+            actions::unban_ip(world, &caller.actor, range).map(|ban| format!("Unbanned {}", ban.range))
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RconAuditEvent;
+
+    fn caller(role: RconRole) -> RconCaller {
+        RconCaller {
+            actor: RconActor::system(),
+            role,
+        }
+    }
+
+    /// Parses the line as the arguments of a command that takes an optional duration and an optional reason.
+    fn parse_ban_args(line: &str) -> Result<(Option<Duration>, Option<RconRest>), String> {
+        let caller = caller(RconRole::SuperAdmin);
+        let mut args = RconArgs {
+            caller: &caller,
+            tokens: tokenize(line),
+            position: 0,
+        };
+        let duration = Option::<Duration>::parse(&mut args)?;
+        let reason = Option::<RconRest>::parse(&mut args)?;
+        args.finish()?;
+        Ok((duration, reason))
+    }
+
+    #[test]
+    fn tokenizes_on_whitespace() {
+        assert_eq!(tokenize("  kick   steam_1\tspam "), ["kick", "steam_1", "spam"]);
+        assert!(tokenize("   ").is_empty());
+    }
+
+    #[test]
+    fn tokenizes_quoted_arguments_together() {
+        assert_eq!(tokenize(r#"say "hello  world" now"#), ["say", "hello  world", "now"]);
+        assert_eq!(tokenize(r#"ban "" x"#), ["ban", "", "x"]);
+        assert_eq!(tokenize(r#"say "unterminated quote"#), ["say", "unterminated quote"]);
+    }
+
+    #[test]
+    fn optional_arguments_are_taken_when_they_parse() {
+        let (duration, reason) = parse_ban_args("7d cheating again").unwrap();
+        assert_eq!(duration, Some(Duration::from_secs(7 * 24 * 60 * 60)));
+        assert_eq!(reason.map(|reason| reason.0), Some("cheating again".to_string()));
+    }
+
+    #[test]
+    fn optional_arguments_can_be_absent() {
+        let (duration, reason) = parse_ban_args("cheating").unwrap();
+        assert_eq!(duration, None);
+        assert_eq!(reason.map(|reason| reason.0), Some("cheating".to_string()));
+
+        let (duration, reason) = parse_ban_args("").unwrap();
+        assert_eq!(duration, None);
+        assert!(reason.is_none());
+    }
+
+    #[test]
+    fn invalid_optional_arguments_are_reported() {
+        let error = parse_ban_args("7x cheating").unwrap_err();
+        assert!(error.contains("Invalid duration 7x"), "{}", error);
+    }
+
+    #[test]
+    fn optional_numbers_only_take_numbers() {
+        let caller = caller(RconRole::SuperAdmin);
+        let mut args = RconArgs {
+            caller: &caller,
+            tokens: tokenize("all"),
+            position: 0,
+        };
+        assert_eq!(Option::<u32>::parse(&mut args), Ok(None));
+        assert_eq!(String::parse(&mut args), Ok("all".to_string()));
+
+        let mut args = RconArgs {
+            caller: &caller,
+            tokens: tokenize("-5"),
+            position: 0,
+        };
+        assert!(Option::<u32>::parse(&mut args).is_err());
+    }
+
+    #[test]
+    fn keeps_game_commands_over_builtin_commands() {
+        let mut app = App::new();
+        app.add_event::<RconAuditEvent>();
+        app.add_rcon_command("kick", |_: &mut World| "The game's kick".to_string());
+        add_builtin_commands(&mut app);
+
+        let admin = caller(RconRole::SuperAdmin);
+        let world = app.world_mut();
+        assert_eq!(RconCommands::execute(world, &admin, "kick"), Ok("The game's kick".to_string()));
+        assert!(world.resource::<RconCommands>().iter().any(|command| command.name == "ban"));
+    }
+
+    #[test]
+    fn executes_commands_with_permission_checks() {
+        let mut app = App::new();
+        app.add_event::<RconAuditEvent>();
+        app.add_rcon_command(
+            RconCommand::new("add").usage("<a> <b>"),
+            |_: &mut World, a: i32, b: i32| format!("{}", a + b),
+        );
+        let world = app.world_mut();
+
+        let admin = caller(RconRole::SuperAdmin);
+        assert_eq!(RconCommands::execute(world, &admin, "add 2 3"), Ok("5".to_string()));
+        assert_eq!(RconCommands::execute(world, &admin, ""), Ok(String::new()));
+        assert_eq!(
+            RconCommands::execute(world, &admin, "nope"),
+            Err(RconCommandError::UnknownCommand("nope".to_string()))
+        );
+        assert_eq!(
+            RconCommands::execute(world, &admin, "add 2 3 4"),
+            Err(RconCommandError::Failed("Too many arguments\nUsage: add <a> <b>".to_string()))
+        );
+        assert!(matches!(RconCommands::execute(world, &admin, "add 2"), Err(RconCommandError::Failed(_))));
+
+        let viewer = caller(RconRole::Viewer);
+        assert_eq!(
+            RconCommands::execute(world, &viewer, "add 2 3"),
+            Err(RconCommandError::Forbidden(RconPermission::RunCommands))
+        );
+    }
+}
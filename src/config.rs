@@ -0,0 +1,65 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use bevy::prelude::*;
+
+use crate::{DbRconAdmin, RconBranding, RconPlayerEntities, RconTemplate, SourceRconConfig};
+
+/// A resource that contains the configuration of the RCON panel.
+/// Inserted by `RconPlugin` from its builder, and read by the webserver setup and the templates at startup.
+#[derive(Resource, Clone)]
+pub struct RconConfig {
+    /// The address the web panel listens on.
+    pub bind: SocketAddr,
+    /// The title shown in the browser tab.
+    pub tab_title: String,
+    /// The game name shown in the panel header.
+    pub game_name: String,
+    /// The server name shown in the panel header.
+    pub server_name: String,
+    /// Admin accounts defined in code. These are not written to the database.
+    pub admins: Vec<DbRconAdmin>,
+    /// How long a login session lasts.
+    pub session_ttl: Duration,
+    /// Whether the panel is behind a reverse proxy that sets `X-Real-IP` or `X-Forwarded-For`,
+    /// so the audit log records the address from those headers. Otherwise no source address is recorded.
+    pub trust_proxy_headers: bool,
+    /// Whether banned players are removed from `RconPlayers` as soon as they are added.
+    pub refuse_banned_players: bool,
+    /// Whether connecting players' identifiers are stored and checked against banned accounts.
+    pub detect_ban_evasion: bool,
+    /// Whether `RconPlayers` is derived from entities with a `RconPlayerComponent`,
+    /// and what happens to them when they are removed. `None` when `RconPlayers` is maintained by hand.
+    pub player_entities: Option<RconPlayerEntities>,
+    /// The Source RCON listener, disabled when `None`.
+    pub source_rcon: Option<SourceRconConfig>,
+    /// The address of the WebSocket listener, disabled when `None`.
+    pub websocket: Option<SocketAddr>,
+    /// The logo, colours and footer links of the default layout.
+    pub branding: RconBranding,
+    /// Replaces the default layout of every page, see `RconPlugin::template`.
+    pub template: Option<RconTemplate>,
+}
+
+impl Default for RconConfig {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8080),
+            tab_title: "RCON Player Management".to_string(),
+            game_name: "Game Name".to_string(),
+            server_name: "Server Name".to_string(),
+            admins: vec![],
+            session_ttl: Duration::from_secs(12 * 60 * 60),
+            trust_proxy_headers: false,
+            refuse_banned_players: false,
+            detect_ban_evasion: false,
+            player_entities: None,
+            source_rcon: None,
+            websocket: None,
+            branding: RconBranding::default(),
+            template: None,
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use axum::http::HeaderMap;
+use bevy::prelude::*;
+use bevy_defer::{AsyncAccess, AsyncWorld};
+use maud::{html, Markup};
+use serde::Deserialize;
+
+use crate::{
+    auth::{session_token, RconSessions},
+    template::render_page,
+    RconActor, RconAdmin, RconCaller, RconCommands, RconForbidden, RconPermission,
+};
+
+/// A command run in the web console, kept in the session so the scrollback survives page loads.
+#[derive(Clone)]
+pub(crate) struct ConsoleEntry {
+    command: String,
+    output: Result<String, String>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ConsoleForm {
+    command: String,
+}
+
+/// The console page, with the commands the admin can run, the scrollback and the command input.
+pub(crate) async fn console_page(
+    admin: axum::Extension<RconAdmin>,
+    headers: HeaderMap,
+) -> Result<axum::response::Html<String>, RconForbidden> {
+    admin.require(RconPermission::ViewPlayers)?;
+
+    let history = session_token(&headers)
+        .and_then(|token| {
+            AsyncWorld
+                .resource::<RconSessions>()
+                .get_mut(|sessions| sessions.console_history(&token))
+                .ok()
+        })
+        .unwrap_or_default();
+
+    let commands = AsyncWorld
+        .resource::<RconCommands>()
+        .get_mut(|commands| {
+            commands
+                .iter()
+                .filter(|command| admin.can(command.permission))
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let content = html! {
+        h3 { "Console" }
+        div id="console-output" class="console-output" {
+            @for entry in &history {
+                (console_entry(entry))
+            }
+        }
+        form
+            hx-post="/console"
+            hx-target="#console-output"
+            hx-swap="beforeend scroll:bottom"
+            hx-on::after-request="if (event.detail.successful) this.reset()"
+        {
+            input type="text" name="command" list="console-history" placeholder="Command" autocomplete="off" autofocus required;
+            datalist id="console-history" {
+                // Most recent first, without repeats.
+                @for command in unique_commands(&history) {
+                    option value=(command) {}
+                }
+            }
+            button type="submit" { "Run" }
+        }
+
+        h3 { "Commands" }
+        table class="console-commands" {
+            thead {
+                tr {
+                    th { "Command" }
+                    th { "Description" }
+                }
+            }
+            tbody {
+                @for command in commands {
+                    tr {
+                        td { code { (command.name) " " (command.usage) } }
+                        td { (command.help) }
+                    }
+                }
+            }
+        }
+    };
+
+    Ok(render_page(Some(&admin), content))
+}
+
+/// Runs a command from the console and returns its entry, which htmx appends to the scrollback.
+/// The command registry checks whether the admin may run the command.
+pub(crate) async fn console_exec(
+    admin: axum::Extension<RconAdmin>,
+    actor: RconActor,
+    headers: HeaderMap,
+    form: axum::extract::Form<ConsoleForm>,
+) -> Result<axum::response::Html<String>, RconForbidden> {
+    admin.require(RconPermission::ViewPlayers)?;
+
+    let command = form.0.command;
+    let caller = RconCaller {
+        actor,
+        role: admin.role,
+    };
+    let entry = AsyncWorld.run({
+        let command = command.clone();
+        move |world: &mut World| ConsoleEntry {
+            output: RconCommands::execute(world, &caller, &command).map_err(|e| e.to_string()),
+            command,
+        }
+    });
+
+    if let Some(token) = session_token(&headers) {
+        let entry = entry.clone();
+        if let Err(e) = AsyncWorld
+            .resource::<RconSessions>()
+            .get_mut(|sessions| sessions.push_console_history(&token, entry))
+        {
+            error!("Failed to store console history: {}", e);
+        }
+    }
+
+    let markup = html! {
+        (console_entry(&entry))
+        // Also offer the command in the history of the input, without reloading the page.
+        datalist hx-swap-oob="afterbegin:#console-history" {
+            option value=(entry.command) {}
+        }
+    };
+
+    Ok(axum::response::Html(markup.into_string()))
+}
+
+fn console_entry(entry: &ConsoleEntry) -> Markup {
+    html! {
+        div class="console-entry" {
+            pre class="console-command" { "> " (entry.command) }
+            @match &entry.output {
+                Ok(output) => pre class="console-result" { (output) },
+                Err(error) => pre class="console-result error" { (error) },
+            }
+        }
+    }
+}
+
+/// The commands in the history, most recent first, without repeats.
+fn unique_commands(history: &[ConsoleEntry]) -> Vec<&str> {
+    let mut commands: Vec<&str> = vec![];
+    for entry in history.iter().rev() {
+        if !commands.contains(&entry.command.as_str()) {
+            commands.push(&entry.command);
+        }
+    }
+    commands
+}
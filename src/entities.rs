@@ -0,0 +1,79 @@
+//! Players as entities, an alternative to maintaining `RconPlayers` by hand.
+//! Enabled with `RconPlugin::player_entities`: every entity with a `RconPlayerComponent` is a connected player,
+//! `RconPlayers` is kept in sync with them, and kicks and bans despawn or tag the entity.
+
+use bevy::prelude::*;
+
+use crate::{RconConfig, RconPlayer, RconPlayers};
+
+/// Marks an entity as a connected player, when `RconPlugin::player_entities` is enabled.
+/// Change the player's metadata through it, and the panel follows.
+#[derive(Component, Clone, Default, Deref, DerefMut, Reflect)]
+pub struct RconPlayerComponent(pub RconPlayer);
+
+/// What happens to a player entity that is kicked, banned or refused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RconPlayerEntities {
+    /// The entity is despawned. The events still carry it, so the game can find the connection.
+    Despawn,
+    /// `RconPlayerRemoved` is added to the entity, and the game despawns it once the player is disconnected.
+    Tag,
+}
+
+/// Added to a player entity that was kicked, banned or refused, with `RconPlayerEntities::Tag`.
+/// Tagged players are no longer listed in `RconPlayers`.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+pub enum RconPlayerRemoved {
+    Kicked,
+    Banned,
+    Refused,
+}
+
+/// Player entities that were added, changed or tagged this frame.
+type ChangedPlayers<'w, 's> = Query<'w, 's, (), Or<(Changed<RconPlayerComponent>, Added<RconPlayerRemoved>)>>;
+
+/// Rebuilds `RconPlayers` from the player entities whenever one is added, changed, tagged or removed.
+pub(crate) fn sync_player_entities(
+    players: Query<&RconPlayerComponent, Without<RconPlayerRemoved>>,
+    changed: ChangedPlayers,
+    mut removed: RemovedComponents<RconPlayerComponent>,
+    mut rcon_players: ResMut<RconPlayers>,
+) {
+    // Always read the removed components, so they are not reported again next frame.
+    let any_removed = removed.read().count() > 0;
+    if !any_removed && changed.is_empty() {
+        return;
+    }
+
+    rcon_players.players = players.iter().map(|player| player.0.clone()).collect();
+}
+
+pub(crate) fn player_entities_enabled(config: Res<RconConfig>) -> bool {
+    config.player_entities.is_some()
+}
+
+/// The entity of the connected player with the given unique ID, if player entities are enabled.
+pub(crate) fn find_player_entity(world: &mut World, unique_id: &str) -> Option<(Entity, RconPlayer)> {
+    world.resource::<RconConfig>().player_entities?;
+
+    let mut players = world.query_filtered::<(Entity, &RconPlayerComponent), Without<RconPlayerRemoved>>();
+    players
+        .iter(world)
+        .find(|(_, player)| player.unique_id == unique_id)
+        .map(|(entity, player)| (entity, player.0.clone()))
+}
+
+/// Despawns or tags a player entity.
+pub(crate) fn remove_player_entity(
+    commands: &mut Commands,
+    mode: RconPlayerEntities,
+    entity: Entity,
+    removed: RconPlayerRemoved,
+) {
+    match mode {
+        RconPlayerEntities::Despawn => commands.entity(entity).despawn_recursive(),
+        RconPlayerEntities::Tag => {
+            commands.entity(entity).insert(removed);
+        }
+    }
+}
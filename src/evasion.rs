@@ -0,0 +1,222 @@
+//! Ban evasion detection, enabled with `RconPlugin::detect_ban_evasion`.
+//! The unique IDs, names, IP addresses and hardware IDs seen together are stored in a persistent identity history.
+//! Connecting players who share an IP address or hardware ID with a banned account are flagged in the player list
+//! and reported with a `RconSuspectedEvasion` event.
+
+use std::{collections::HashMap, net::IpAddr};
+
+use bevy::{ecs::event::EventCursor, prelude::*};
+use maud::{html, Markup};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audit::unix_now, entities, DbRconBanExpiry, DbRconBannedPlayer, RconConfig, RconPlayer, RconPlayerRefused,
+    RconPlayers,
+};
+
+/// How many names, IP addresses and hardware IDs are kept per unique ID. The oldest are dropped first.
+const IDENTITY_HISTORY_LIMIT: usize = 32;
+
+/// The names, IP addresses and hardware IDs seen with a unique ID, stored in the database.
+#[derive(Component, Clone, Default, Serialize, Deserialize, PartialEq, Reflect)]
+pub struct DbRconIdentity {
+    pub unique_id: String,
+    /// Oldest first, like the other histories.
+    pub names: Vec<String>,
+    #[reflect(ignore)]
+    pub ips: Vec<IpAddr>,
+    pub hardware_ids: Vec<String>,
+    /// Seconds since the unix epoch.
+    pub first_seen: u64,
+    /// Seconds since the unix epoch, updated when the player connects.
+    pub last_seen: u64,
+}
+
+impl DbRconIdentity {
+    /// Adds the player's current name, IP address and hardware ID to the history.
+    fn record(&mut self, player: &RconPlayer) {
+        remember(&mut self.names, player.name.clone());
+        if let Some(ip) = player.ip {
+            remember(&mut self.ips, ip.to_canonical());
+        }
+        if let Some(hardware_id) = &player.hardware_id {
+            remember(&mut self.hardware_ids, hardware_id.clone());
+        }
+        self.last_seen = unix_now();
+    }
+}
+
+/// Moves the value to the end of the history, dropping the oldest values past the limit.
+fn remember<T: PartialEq>(history: &mut Vec<T>, value: T) {
+    history.retain(|seen| *seen != value);
+    history.push(value);
+    if history.len() > IDENTITY_HISTORY_LIMIT {
+        history.remove(0);
+    }
+}
+
+/// An identifier a player shares with a banned account.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum RconSharedIdentifier {
+    Ip(IpAddr),
+    HardwareId(String),
+}
+
+/// A banned account that a player shares identifiers with.
+#[derive(Clone, Serialize)]
+pub struct RconEvasionLink {
+    pub banned: DbRconBannedPlayer,
+    pub shared: Vec<RconSharedIdentifier>,
+}
+
+/// An event that is sent to the plugin user when a player connects with the IP address or hardware ID
+/// of a banned account. The player stays connected, the game decides what to do, e.g. ban them or alert a moderator.
+#[derive(Event)]
+pub struct RconSuspectedEvasion {
+    pub player: RconPlayer,
+    pub links: Vec<RconEvasionLink>,
+    /// The player's entity, if player entities are enabled.
+    pub entity: Option<Entity>,
+}
+
+pub(crate) fn detect_ban_evasion_enabled(config: Res<RconConfig>) -> bool {
+    config.detect_ban_evasion
+}
+
+/// Records the identifiers of players that joined or were refused, and reports joined players
+/// that are linked to a banned account. Runs after the join hook, so refused players are not reported.
+pub(crate) fn track_identities(
+    world: &mut World,
+    mut known: Local<Vec<String>>,
+    mut refused: Local<EventCursor<RconPlayerRefused>>,
+) {
+    let players = world.resource::<RconPlayers>().players.clone();
+    let joined: Vec<RconPlayer> = players
+        .iter()
+        .filter(|player| !known.contains(&player.unique_id))
+        .cloned()
+        .collect();
+    *known = players.into_iter().map(|player| player.unique_id).collect();
+
+    // Refused players are recorded too, so that new addresses of a banned account are linked to it.
+    let refused: Vec<RconPlayer> = refused
+        .read(world.resource::<Events<RconPlayerRefused>>())
+        .map(|event| event.player.clone())
+        .collect();
+
+    for player in joined.iter().chain(&refused) {
+        record_identity(world, player);
+    }
+
+    let mut links = find_links(world, &joined);
+    for player in joined {
+        let Some(links) = links.remove(&player.unique_id) else {
+            continue;
+        };
+
+        let banned: Vec<String> = links.iter().map(|link| link.banned.unique_id.clone()).collect();
+        warn!("Possible ban evasion: {} shares identifiers with banned {}", player, banned.join(", "));
+        let entity = entities::find_player_entity(world, &player.unique_id).map(|(entity, _)| entity);
+        world.send_event(RconSuspectedEvasion { player, links, entity });
+    }
+}
+
+/// Adds the player's identifiers to their identity, creating it when they are seen for the first time.
+fn record_identity(world: &mut World, player: &RconPlayer) {
+    let mut identities = world.query::<&mut DbRconIdentity>();
+    if let Some(mut identity) = identities
+        .iter_mut(world)
+        .find(|identity| identity.unique_id == player.unique_id)
+    {
+        identity.record(player);
+        return;
+    }
+
+    let mut identity = DbRconIdentity {
+        unique_id: player.unique_id.clone(),
+        first_seen: unix_now(),
+        ..default()
+    };
+    identity.record(player);
+    world.spawn(identity);
+}
+
+/// The banned accounts each player is linked to, by unique ID. Players without links are left out.
+/// A player is linked to a banned account when their current IP address or hardware ID
+/// was ever seen with it. Expired bans are ignored.
+pub(crate) fn find_links(world: &mut World, players: &[RconPlayer]) -> HashMap<String, Vec<RconEvasionLink>> {
+    if players.is_empty() {
+        return HashMap::new();
+    }
+
+    let now = unix_now();
+    let mut bans = world.query::<(&DbRconBannedPlayer, Option<&DbRconBanExpiry>)>();
+    let banned: Vec<DbRconBannedPlayer> = bans
+        .iter(world)
+        .filter(|(_, expiry)| expiry.is_none_or(|expiry| expiry.expires_at > now))
+        .map(|(banned, _)| banned.clone())
+        .collect();
+    if banned.is_empty() {
+        return HashMap::new();
+    }
+
+    let mut identities = world.query::<&DbRconIdentity>();
+    let identities: HashMap<&str, &DbRconIdentity> = identities
+        .iter(world)
+        .map(|identity| (identity.unique_id.as_str(), identity))
+        .collect();
+
+    let mut links = HashMap::new();
+    for player in players {
+        let player_links: Vec<RconEvasionLink> = banned
+            .iter()
+            .filter(|banned| banned.unique_id != player.unique_id)
+            .filter_map(|banned| {
+                let identity = identities.get(banned.unique_id.as_str())?;
+                let ip = player
+                    .ip
+                    .map(|ip| ip.to_canonical())
+                    .filter(|ip| identity.ips.contains(ip))
+                    .map(RconSharedIdentifier::Ip);
+                let hardware_id = player
+                    .hardware_id
+                    .clone()
+                    .filter(|hardware_id| identity.hardware_ids.contains(hardware_id))
+                    .map(RconSharedIdentifier::HardwareId);
+                let shared: Vec<RconSharedIdentifier> = ip.into_iter().chain(hardware_id).collect();
+
+                (!shared.is_empty()).then(|| RconEvasionLink {
+                    banned: banned.clone(),
+                    shared,
+                })
+            })
+            .collect();
+
+        if !player_links.is_empty() {
+            links.insert(player.unique_id.clone(), player_links);
+        }
+    }
+
+    links
+}
+
+/// The warning shown next to a linked player's name in the player list.
+/// Only says what kind of identifier is shared, not its value.
+pub(crate) fn evasion_flag(links: &[RconEvasionLink]) -> Markup {
+    html! {
+        @for link in links {
+            span class="evasion-flag" {
+                "Possible ban evasion: shares "
+                @for (index, shared) in link.shared.iter().enumerate() {
+                    @if index > 0 { " and " }
+                    @match shared {
+                        RconSharedIdentifier::Ip(_) => "an IP address",
+                        RconSharedIdentifier::HardwareId(_) => "a hardware ID",
+                    }
+                }
+                " with banned " (link.banned.name) " (ID: " (link.banned.unique_id) ")"
+            }
+        }
+    }
+}
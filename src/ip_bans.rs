@@ -0,0 +1,386 @@
+//! Bans by IP address or CIDR range, for players who evade ID bans with fresh accounts.
+//! The game sets `RconPlayer::ip` and checks `RconBans::is_ip_banned` when a player connects,
+//! and connected players in a newly banned range are removed like banned players.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use bevy_defer::{AsyncAccess, AsyncWorld};
+use maud::{html, Markup};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions,
+    audit::{self, unix_now},
+    bans, parse_duration, RconActor, RconAdmin, RconBanOptions, RconForbidden,
+    RconPermission, RconPlayer,
+};
+
+/// An IP address or a CIDR range of addresses, like `203.0.113.7`, `203.0.113.0/24` or `2001:db8::/32`.
+/// Host bits are cleared when parsing, so `203.0.113.7/24` is the same range as `203.0.113.0/24`.
+/// Serialized as its text form.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Reflect)]
+#[serde(into = "String", try_from = "String")]
+#[reflect(opaque)]
+pub struct RconIpRange {
+    address: IpAddr,
+    prefix: u8,
+}
+
+impl RconIpRange {
+    /// The range of all addresses that start with the first `prefix` bits of `address`.
+    /// IPv4 addresses mapped to IPv6 are converted to IPv4 ranges, so `::ffff:203.0.113.0/120`
+    /// is the same range as `203.0.113.0/24`.
+    pub fn new(address: IpAddr, prefix: u8) -> Result<Self, RconInvalidIpRange> {
+        let (address, prefix) = match address {
+            IpAddr::V6(v6) if (96..=128).contains(&prefix) => match v6.to_ipv4_mapped() {
+                Some(v4) => (IpAddr::V4(v4), prefix - 96),
+                None => (address, prefix),
+            },
+            _ => (address, prefix),
+        };
+        let address = match address {
+            IpAddr::V4(v4) if prefix <= 32 => IpAddr::V4(Ipv4Addr::from(u32::from(v4) & v4_mask(prefix))),
+            IpAddr::V6(v6) if prefix <= 128 => IpAddr::V6(Ipv6Addr::from(u128::from(v6) & v6_mask(prefix))),
+            _ => return Err(RconInvalidIpRange(format!("{}/{}", address, prefix))),
+        };
+
+        Ok(Self { address, prefix })
+    }
+
+    /// The range that only contains `address`.
+    pub fn single(address: IpAddr) -> Self {
+        let address = address.to_canonical();
+        let prefix = if address.is_ipv4() { 32 } else { 128 };
+        Self { address, prefix }
+    }
+
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    /// How many leading bits of an address have to match.
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    /// Whether the address is in the range. IPv4 addresses mapped to IPv6, like `::ffff:203.0.113.7`,
+    /// match IPv4 ranges.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.address, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => u32::from(ip) & v4_mask(self.prefix) == u32::from(network),
+            (IpAddr::V6(network), IpAddr::V6(ip)) => u128::from(ip) & v6_mask(self.prefix) == u128::from(network),
+            _ => false,
+        }
+    }
+
+    fn is_single(&self) -> bool {
+        self.prefix == if self.address.is_ipv4() { 32 } else { 128 }
+    }
+}
+
+fn v4_mask(prefix: u8) -> u32 {
+    u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0)
+}
+
+fn v6_mask(prefix: u8) -> u128 {
+    u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0)
+}
+
+impl std::fmt::Display for RconIpRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.is_single() {
+            true => write!(f, "{}", self.address),
+            false => write!(f, "{}/{}", self.address, self.prefix),
+        }
+    }
+}
+
+impl FromStr for RconIpRange {
+    type Err = RconInvalidIpRange;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+        let invalid = || RconInvalidIpRange(input.to_string());
+        match input.split_once('/') {
+            Some((address, prefix)) => {
+                let address = address.parse().map_err(|_| invalid())?;
+                let prefix = prefix.parse().map_err(|_| invalid())?;
+                Self::new(address, prefix)
+            }
+            None => input.parse().map(Self::single).map_err(|_| invalid()),
+        }
+    }
+}
+
+impl From<IpAddr> for RconIpRange {
+    fn from(address: IpAddr) -> Self {
+        Self::single(address)
+    }
+}
+
+impl From<RconIpRange> for String {
+    fn from(range: RconIpRange) -> Self {
+        range.to_string()
+    }
+}
+
+impl TryFrom<String> for RconIpRange {
+    type Error = RconInvalidIpRange;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        input.parse()
+    }
+}
+
+/// The error returned when parsing a `RconIpRange` fails, with the input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RconInvalidIpRange(pub String);
+
+impl std::fmt::Display for RconInvalidIpRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid IP address or range {}, expected e.g. 203.0.113.7 or 203.0.113.0/24", self.0)
+    }
+}
+
+impl std::error::Error for RconInvalidIpRange {}
+
+/// A ban of an IP address or range, stored in the database.
+#[derive(Component, Clone, Serialize, Deserialize, PartialEq, Reflect)]
+pub struct DbRconIpBan {
+    pub range: RconIpRange,
+    pub reason: Option<String>,
+    /// The username of the admin that issued the ban.
+    pub issued_by: String,
+    /// Seconds since the unix epoch.
+    pub created_at: u64,
+    /// Free-form notes, e.g. links to evidence.
+    pub notes: Option<String>,
+    /// When the ban ends in seconds since the unix epoch, or `None` for a permanent ban.
+    pub expires_at: Option<u64>,
+}
+
+impl DbRconIpBan {
+    /// Whether the ban has not expired yet.
+    pub fn is_active(&self) -> bool {
+        self.expires_at.is_none_or(|expires_at| expires_at > unix_now())
+    }
+
+    /// How long until the ban ends, `None` for a permanent ban.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.expires_at
+            .map(|expires_at| Duration::from_secs(expires_at.saturating_sub(unix_now())))
+    }
+}
+
+/// An event that is sent to the plugin user when an IP address or range is banned.
+/// Connected players in the range are removed from `RconPlayers`, and their entities despawned or tagged
+/// if player entities are enabled. The plugin user can then disconnect them.
+#[derive(Event)]
+pub struct RconIpBanned {
+    pub ban: DbRconIpBan,
+    /// The connected players in the range.
+    pub players: Vec<RconPlayer>,
+    /// The entities of those players, if player entities are enabled.
+    pub entities: Vec<Entity>,
+}
+
+/// An event that is sent to the plugin user when an IP ban is lifted.
+#[derive(Event)]
+pub struct RconIpUnbanned {
+    pub range: RconIpRange,
+}
+
+/// The form submitted to add an IP ban.
+#[derive(Deserialize)]
+pub(crate) struct IpBanForm {
+    range: String,
+    /// A duration like `30m` or `7d`, empty for a permanent ban.
+    #[serde(default)]
+    duration: String,
+    #[serde(default)]
+    reason: String,
+    #[serde(default)]
+    notes: String,
+}
+
+/// The form submitted by the unban button of an IP ban.
+#[derive(Deserialize)]
+pub(crate) struct IpUnbanForm {
+    range: String,
+}
+
+/// The IP bans section of the ban list, with a form to add one.
+/// Empty for admins who can not ban, who are not shown addresses.
+pub(crate) fn ip_ban_list(admin: &RconAdmin) -> Markup {
+    if !admin.can(RconPermission::Ban) {
+        return html! {};
+    }
+
+    let ip_bans = AsyncWorld
+        .query::<&DbRconIpBan>()
+        .get_mut(|mut query| query.iter().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    html! {
+        h3 { "Banned IP Addresses" }
+        div class="ip-ban-list" {
+            @for ban in ip_bans {
+                div class="banned-player" {
+                    span { code { (ban.range) } }
+                    @match ban.remaining() {
+                        Some(remaining) => span class="ban-expiry" { "Expires in " (bans::format_duration(remaining)) },
+                        None => span class="ban-expiry" { "Permanent" },
+                    }
+                    span class="ban-details" {
+                        "Banned by " (ban.issued_by) " on " (audit::format_timestamp(ban.created_at))
+                    }
+                    @if let Some(reason) = &ban.reason {
+                        span class="ban-reason" { "Reason: " (reason) }
+                    }
+                    @if let Some(notes) = &ban.notes {
+                        span class="ban-notes" { "Notes: " (notes) }
+                    }
+                    @if admin.can(RconPermission::Unban) {
+                        form hx-post="/unban_ip" hx-target="body" hx-swap="innerHTML" {
+                            input type="hidden" name="range" value=(ban.range);
+                            button type="submit" { "Unban" }
+                        }
+                    }
+                }
+            }
+        }
+        form class="ip-ban-form" hx-post="/ban_ip" hx-target="body" hx-swap="innerHTML" {
+            input type="text" name="range" placeholder="IP or range, e.g. 203.0.113.0/24" required;
+            input type="text" name="duration" placeholder="Duration, e.g. 7d (empty for permanent)";
+            input type="text" name="reason" placeholder="Reason";
+            input type="text" name="notes" placeholder="Notes (optional)";
+            button type="submit" { "Ban IP" }
+        }
+    }
+}
+
+/// Bans an IP address or range (database update).
+/// Also removes connected players in the range and sends a `RconIpBanned` event.
+pub(crate) async fn ban_ip(
+    admin: axum::Extension<RconAdmin>,
+    actor: RconActor,
+    form: axum::extract::Form<IpBanForm>,
+) -> Result<axum::response::Html<String>, RconForbidden> {
+    admin.require(RconPermission::Ban)?;
+    let IpBanForm { range, duration, reason, notes } = form.0;
+    let non_empty = |value: String| Some(value.trim().to_string()).filter(|value| !value.is_empty());
+
+    let range: RconIpRange = match range.parse() {
+        Ok(range) => range,
+        Err(e) => {
+            warn!("Failed to ban IP: {}", e);
+            return crate::index(admin).await;
+        }
+    };
+    let duration = match non_empty(duration) {
+        Some(input) => match parse_duration(&input) {
+            Some(duration) => Some(duration),
+            None => {
+                warn!("Invalid ban duration: {}", input);
+                return crate::index(admin).await;
+            }
+        },
+        None => None,
+    };
+
+    let options = RconBanOptions {
+        duration,
+        reason: non_empty(reason),
+        notes: non_empty(notes),
+    };
+
+    if let Err(e) = AsyncWorld.run(move |world: &mut World| actions::ban_ip(world, &actor, range, options)) {
+        warn!("Failed to ban IP: {}", e);
+    }
+
+    crate::index(admin).await
+}
+
+/// Lifts an IP ban (database update). Also sends a `RconIpUnbanned` event.
+pub(crate) async fn unban_ip(
+    admin: axum::Extension<RconAdmin>,
+    actor: RconActor,
+    form: axum::extract::Form<IpUnbanForm>,
+) -> Result<axum::response::Html<String>, RconForbidden> {
+    admin.require(RconPermission::Unban)?;
+
+    match form.range.parse::<RconIpRange>() {
+        Ok(range) => {
+            if let Err(e) = AsyncWorld.run(move |world: &mut World| actions::unban_ip(world, &actor, range)) {
+                warn!("Failed to unban IP: {}", e);
+            }
+        }
+        Err(e) => warn!("Failed to unban IP: {}", e),
+    }
+
+    crate::index(admin).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(input: &str) -> RconIpRange {
+        input.parse().unwrap()
+    }
+
+    fn ip(input: &str) -> IpAddr {
+        input.parse().unwrap()
+    }
+
+    #[test]
+    fn clears_host_bits() {
+        assert_eq!(range("203.0.113.7/24"), range("203.0.113.0/24"));
+        assert_eq!(range("203.0.113.7/24").address(), ip("203.0.113.0"));
+        assert_eq!(range("2001:db8::1/32").address(), ip("2001:db8::"));
+    }
+
+    #[test]
+    fn matches_by_prefix() {
+        assert!(range("0.0.0.0/0").contains(ip("198.51.100.1")));
+        assert!(range("::/0").contains(ip("2001:db8::1")));
+        assert!(range("203.0.113.7/32").contains(ip("203.0.113.7")));
+        assert!(!range("203.0.113.7/32").contains(ip("203.0.113.8")));
+        assert!(range("2001:db8::1/128").contains(ip("2001:db8::1")));
+        assert!(!range("2001:db8::1/128").contains(ip("2001:db8::2")));
+        assert!(range("203.0.113.0/24").contains(ip("203.0.113.255")));
+        assert!(!range("203.0.113.0/24").contains(ip("203.0.114.0")));
+        assert!(!range("0.0.0.0/0").contains(ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn rejects_invalid_ranges() {
+        assert!("203.0.113.0/33".parse::<RconIpRange>().is_err());
+        assert!("2001:db8::/129".parse::<RconIpRange>().is_err());
+        assert!("203.0.113.0/".parse::<RconIpRange>().is_err());
+        assert!("example.com".parse::<RconIpRange>().is_err());
+    }
+
+    #[test]
+    fn treats_mapped_ipv4_as_ipv4() {
+        assert!(range("203.0.113.0/24").contains(ip("::ffff:203.0.113.7")));
+        assert_eq!(range("::ffff:203.0.113.7"), range("203.0.113.7"));
+        assert_eq!(range("::ffff:203.0.113.0/120"), range("203.0.113.0/24"));
+        assert!(range("::ffff:203.0.113.0/120").contains(ip("203.0.113.7")));
+        assert!(range("::ffff:203.0.113.0/120").contains(ip("::ffff:203.0.113.7")));
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        for input in ["203.0.113.7", "203.0.113.0/24", "0.0.0.0/0", "2001:db8::/32", "2001:db8::1", "::/0"] {
+            assert_eq!(range(input).to_string(), input);
+            assert_eq!(range(&range(input).to_string()), range(input));
+        }
+        assert_eq!(range("::ffff:203.0.113.0/120").to_string(), "203.0.113.0/24");
+    }
+}
@@ -1,29 +1,342 @@
+mod actions;
+mod api;
+mod assets;
+mod audit;
+mod auth;
+mod bans;
+mod commands;
+mod config;
+mod console;
+mod entities;
+mod evasion;
+mod ip_bans;
+mod live;
+mod logs;
+mod metadata;
+mod pages;
+mod player_actions;
+mod roles;
+mod source_rcon;
 mod template;
+mod tokens;
+mod websocket;
 
-use bevy::{prelude::*, utils::HashMap};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::IpAddr,
+};
+
+use bevy::{prelude::*, time::common_conditions::on_real_timer};
 use bevy_defer::{AsyncAccess, AsyncWorld};
 use bevy_easy_database::{AddDatabaseMapping, DatabasePlugin};
-use bevy_webserver::{BevyWebServerPlugin, RouterAppExt};
+use bevy_webserver::{BevyWebServerPlugin, RouterAppExt, WebServerConfig};
 use maud::{html, Markup};
 use serde::{Deserialize, Serialize};
-use template::{base_template, TemplateParams};
+use auth::RconSessions;
+use metadata::PlayerSort;
+use template::render_page;
+
+pub use actions::RconActionError;
+pub use audit::{DbRconAuditEntry, RconActor, RconAuditEvent};
+pub use auth::{hash_password, DbRconAdmin, RconAdmin};
+pub use bans::{
+    parse_duration, DbRconBanDetails, DbRconBanExpiry, RconBanInfo, RconBanOptions, RconBans,
+    RconPlayerRefused,
+};
+pub use commands::{
+    IntoRconOutput, RconAppExt, RconArg, RconArgs, RconCaller, RconCommand, RconCommandError, RconCommandHandler,
+    RconCommands, RconRest,
+};
+pub use config::RconConfig;
+pub use entities::{RconPlayerComponent, RconPlayerEntities, RconPlayerRemoved};
+pub use evasion::{DbRconIdentity, RconEvasionLink, RconSharedIdentifier, RconSuspectedEvasion};
+pub use ip_bans::{DbRconIpBan, RconInvalidIpRange, RconIpBanned, RconIpRange, RconIpUnbanned};
+pub use logs::{rcon_log_layer, RconLogEntry, RconLogFilter, RconLogs};
+pub use metadata::RconValue;
+pub use pages::{RconPage, RconPageRequest};
+pub use player_actions::{RconActionField, RconFieldKind, RconPlayerAction, RconPlayerActionEvent};
+pub use roles::{RconForbidden, RconPermission, RconRole};
+pub use source_rcon::SourceRconConfig;
+pub use template::{base_template, RconBranding, RconLink, RconTemplate, TemplateParams};
+pub use tokens::DbRconApiToken;
+
+/// The RCON plugin. Configure it with the builder methods, e.g.
+/// `RconPlugin::new().bind("127.0.0.1:8080").game_name("My Game").server_name("EU #1")`.
+#[derive(Default)]
+pub struct RconPlugin {
+    config: RconConfig,
+}
+
+impl RconPlugin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the address the web panel listens on, e.g. `"0.0.0.0:8080"`.
+    ///
+    /// # Panics
+    /// Panics if the address can not be parsed as a socket address.
+    pub fn bind(mut self, address: impl AsRef<str>) -> Self {
+        let address = address.as_ref();
+        self.config.bind = address
+            .parse()
+            .unwrap_or_else(|e| panic!("Invalid RCON bind address {}: {}", address, e));
+        self
+    }
+
+    /// Sets the port the web panel listens on, keeping the bound IP address.
+    pub fn port(mut self, port: u16) -> Self {
+        self.config.bind.set_port(port);
+        self
+    }
+
+    /// Adds an admin account that can log into the panel.
+    /// The password hash can be created with `hash_password`.
+    pub fn admin(
+        mut self,
+        username: impl Into<String>,
+        password_hash: impl Into<String>,
+        role: RconRole,
+    ) -> Self {
+        self.config.admins.push(DbRconAdmin {
+            username: username.into(),
+            password_hash: password_hash.into(),
+            role,
+        });
+        self
+    }
+
+    /// Sets how long a login session lasts. Defaults to 12 hours.
+    pub fn session_ttl(mut self, session_ttl: std::time::Duration) -> Self {
+        self.config.session_ttl = session_ttl;
+        self
+    }
+
+    /// Records the client address from the `X-Real-IP` or `X-Forwarded-For` header in the audit log.
+    /// Only enable this behind a reverse proxy that sets these headers, since clients can send them too.
+    /// Disabled by default, in which case no source address is recorded.
+    pub fn trust_proxy_headers(mut self, trust_proxy_headers: bool) -> Self {
+        self.config.trust_proxy_headers = trust_proxy_headers;
+        self
+    }
+
+    /// Enables the join hook, which removes banned players from `RconPlayers` as soon as they are added
+    /// and sends a `RconPlayerRefused` event, so ban enforcement lives in one place.
+    /// Players are also refused when their `RconPlayer::ip` is in a banned range.
+    /// Disabled by default, in which case game code should check `RconBans::is_banned`
+    /// and `RconBans::is_ip_banned` on connect.
+    pub fn refuse_banned_players(mut self, refuse_banned_players: bool) -> Self {
+        self.config.refuse_banned_players = refuse_banned_players;
+        self
+    }
+
+    /// Enables ban evasion detection, which stores the unique IDs, names, IP addresses and hardware IDs of
+    /// connecting players in the database. Players who share an IP address or hardware ID with a banned account
+    /// are flagged in the player list, and a `RconSuspectedEvasion` event is sent. Disabled by default.
+    pub fn detect_ban_evasion(mut self, detect_ban_evasion: bool) -> Self {
+        self.config.detect_ban_evasion = detect_ban_evasion;
+        self
+    }
+
+    /// Derives `RconPlayers` from the entities with a `RconPlayerComponent`, instead of maintaining it by hand.
+    /// Spawn an entity when a player connects and despawn it when they disconnect.
+    /// Kicked, banned and refused players' entities are despawned or tagged with `RconPlayerRemoved`, depending on `mode`.
+    pub fn player_entities(mut self, mode: RconPlayerEntities) -> Self {
+        self.config.player_entities = Some(mode);
+        self
+    }
+
+    /// Enables a listener for the Source RCON protocol next to the web panel, so existing RCON clients can connect.
+    /// Clients that authenticate with the password can run every command.
+    ///
+    /// # Panics
+    /// Panics if the address can not be parsed as a socket address, or if the password is empty.
+    pub fn source_rcon(mut self, address: impl AsRef<str>, password: impl Into<String>) -> Self {
+        let address = address.as_ref();
+        let password = password.into();
+        // An empty auth packet would otherwise log in with full permissions.
+        if password.is_empty() {
+            panic!("The Source RCON password can not be empty");
+        }
+        self.config.source_rcon = Some(SourceRconConfig {
+            bind: address
+                .parse()
+                .unwrap_or_else(|e| panic!("Invalid Source RCON bind address {}: {}", address, e)),
+            password,
+        });
+        self
+    }
+
+    /// Enables the WebSocket endpoint for interactive administration, served at `ws://<address>/ws`.
+    /// It needs its own address, because the web panel can not upgrade connections.
+    ///
+    /// # Panics
+    /// Panics if the address can not be parsed as a socket address.
+    pub fn websocket(mut self, address: impl AsRef<str>) -> Self {
+        let address = address.as_ref();
+        self.config.websocket = Some(
+            address
+                .parse()
+                .unwrap_or_else(|e| panic!("Invalid WebSocket bind address {}: {}", address, e)),
+        );
+        self
+    }
 
-pub struct RconPlugin;
+    /// Sets the title shown in the browser tab.
+    pub fn tab_title(mut self, tab_title: impl Into<String>) -> Self {
+        self.config.tab_title = tab_title.into();
+        self
+    }
+
+    /// Sets the game name shown in the panel header.
+    pub fn game_name(mut self, game_name: impl Into<String>) -> Self {
+        self.config.game_name = game_name.into();
+        self
+    }
+
+    /// Sets the server name shown in the panel header.
+    pub fn server_name(mut self, server_name: impl Into<String>) -> Self {
+        self.config.server_name = server_name.into();
+        self
+    }
+
+    /// Shows a logo next to the game name, e.g. `"https://example.com/logo.png"`.
+    pub fn logo(mut self, logo_url: impl Into<String>) -> Self {
+        self.config.branding.logo_url = Some(logo_url.into());
+        self
+    }
+
+    /// Sets the colour of buttons and links, any CSS colour such as `"#e0513b"`.
+    pub fn accent_color(mut self, accent_color: impl Into<String>) -> Self {
+        self.config.branding.accent_color = Some(accent_color.into());
+        self
+    }
+
+    /// Adds a link to the footer of every page.
+    pub fn footer_link(mut self, title: impl Into<String>, href: impl Into<String>) -> Self {
+        self.config.branding.footer_links.push(RconLink::new(title, href));
+        self
+    }
+
+    /// Replaces the default layout of every page. The template must include `params.head` in its `head`,
+    /// and can call `base_template` to wrap the default layout instead of replacing it.
+    pub fn template(mut self, template: impl Fn(TemplateParams) -> Markup + Send + Sync + 'static) -> Self {
+        self.config.template = Some(std::sync::Arc::new(template));
+        self
+    }
+}
 
 impl Plugin for RconPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins((
+        app.insert_resource(WebServerConfig {
+            ip: self.config.bind.ip(),
+            port: self.config.bind.port(),
+        })
+        .insert_resource(self.config.clone())
+        .add_plugins((
             BevyWebServerPlugin,
             DatabasePlugin,
         ))
         .insert_resource(RconPlayers { players: vec![] })
+        .init_resource::<RconSessions>()
+        .init_resource::<RconCommands>()
+        .init_resource::<pages::RconPages>()
+        .init_resource::<player_actions::RconPlayerActions>()
+        .init_resource::<live::RconLiveClients>()
+        .init_resource::<websocket::RconWsClients>()
+        .init_resource::<source_rcon::SourceRconLogins>()
+        // Already inserted by `rcon_log_layer` when the logs are captured.
+        .init_resource::<RconLogs>()
+        .add_event::<RconPlayerBanned>()
+        .add_event::<RconPlayerUnbanned>()
+        .add_event::<RconPlayerKicked>()
+        .add_event::<RconAuditEvent>()
+        .add_event::<RconPlayerRefused>()
+        .add_event::<RconIpBanned>()
+        .add_event::<RconIpUnbanned>()
+        .add_event::<RconSuspectedEvasion>()
         .add_database_mapping::<DbRconBannedPlayer>()
+        .add_database_mapping::<DbRconBanExpiry>()
+        .add_database_mapping::<DbRconBanDetails>()
+        .add_database_mapping::<DbRconIpBan>()
+        .add_database_mapping::<DbRconIdentity>()
+        .add_database_mapping::<DbRconAdmin>()
+        .add_database_mapping::<DbRconAuditEntry>()
+        .add_database_mapping::<DbRconApiToken>()
+        .add_systems(
+            Update,
+            bans::lift_expired_bans.run_if(on_real_timer(std::time::Duration::from_secs(1))),
+        )
+        .add_systems(
+            Update,
+            live::keep_alive.run_if(on_real_timer(std::time::Duration::from_secs(15))),
+        )
+        // Runs late, so that changes made by the game during the frame are sent in the same frame.
+        .add_systems(
+            PostUpdate,
+            (
+                (live::broadcast_changes, live::broadcast_ip_ban_changes).after(live::check_live_clients),
+                live::check_live_clients,
+                websocket::broadcast_ws_events,
+            ),
+        )
+        .add_systems(Startup, (source_rcon::start_source_rcon, websocket::start_websocket))
+        .add_systems(
+            PreUpdate,
+            (
+                entities::sync_player_entities.run_if(entities::player_entities_enabled),
+                bans::refuse_banned_players.run_if(
+                    resource_changed::<RconPlayers>.and(refuse_banned_players_enabled),
+                ),
+                evasion::track_identities.run_if(
+                    resource_changed::<RconPlayers>.and(evasion::detect_ban_evasion_enabled),
+                ),
+            )
+                .chain(),
+        );
+        commands::add_builtin_commands(app);
+
+        app
         // Routes
+        .route("/login", axum::routing::get(auth::login_page).post(auth::login))
+        .route("/logout", axum::routing::post(auth::logout))
+        .route("/static/{file}", axum::routing::get(assets::serve_static))
         .route("/", axum::routing::get(index))
         .route("/players", axum::routing::get(list_players))
         .route("/ban_list", axum::routing::get(list_bans))
+        .route("/kick_player", axum::routing::post(kick_player))
         .route("/ban_player", axum::routing::post(ban_player))
-        .route("/unban_player/{id}", axum::routing::post(unban_player));
+        .route("/unban_player/{id}", axum::routing::post(unban_player))
+        .route("/ban_ip", axum::routing::post(ip_bans::ban_ip))
+        .route("/unban_ip", axum::routing::post(ip_bans::unban_ip))
+        .route("/audit", axum::routing::get(audit::audit_page))
+        .route("/events", axum::routing::get(live::events))
+        .route("/logs", axum::routing::get(logs::logs_page))
+        .route("/logs/tail", axum::routing::get(logs::logs_tail))
+        .route("/console", axum::routing::get(console::console_page).post(console::console_exec))
+        .route("/tokens", axum::routing::get(tokens::tokens_page).post(tokens::create_token))
+        .route("/tokens/{prefix}/revoke", axum::routing::post(tokens::revoke_token))
+        // JSON API
+        .route("/api/v1/players", axum::routing::get(api::players))
+        .route("/api/v1/bans", axum::routing::get(api::bans).post(api::ban))
+        .route("/api/v1/bans/{id}", axum::routing::delete(api::unban))
+        .route("/api/v1/ip_bans", axum::routing::get(api::ip_bans).post(api::ban_ip))
+        // A wildcard, because ranges contain a slash.
+        .route("/api/v1/ip_bans/{*range}", axum::routing::delete(api::unban_ip))
+        .route("/api/v1/kick", axum::routing::post(api::kick))
+        .route("/api/v1/audit", axum::routing::get(api::audit))
+        .route("/api/v1/commands", axum::routing::post(api::command))
+        .route("/api/v1/events", axum::routing::get(live::events))
+        .route("/api/v1/logs", axum::routing::get(logs::logs_json));
+    }
+
+    fn finish(&self, app: &mut App) {
+        if self.config.admins.is_empty() {
+            warn!("No RCON admins are configured in code, only admins stored in the database can log in.");
+        }
+
+        // Applied last, so that it also covers routes added after the plugin was built.
+        app.layer(axum::middleware::from_fn(auth::require_session));
     }
 }
 
@@ -32,6 +345,18 @@ impl Plugin for RconPlugin {
 #[derive(Event)]
 pub struct RconPlayerBanned {
     pub player: RconPlayer,
+    /// When the ban ends in seconds since the unix epoch, or `None` for a permanent ban.
+    pub expires_at: Option<u64>,
+    /// The reason given by the admin, if any.
+    pub reason: Option<String>,
+    /// The player's entity, if player entities are enabled and the player was connected.
+    pub entity: Option<Entity>,
+}
+
+/// An event that is sent to the plugin user when a player is unbanned.
+#[derive(Event)]
+pub struct RconPlayerUnbanned {
+    pub player: RconPlayer,
 }
 
 /// An event that is sent to the plugin user when a player is kicked.
@@ -39,6 +364,10 @@ pub struct RconPlayerBanned {
 #[derive(Event)]
 pub struct RconPlayerKicked {
     pub player: RconPlayer,
+    /// The reason given by the admin, if any.
+    pub reason: Option<String>,
+    /// The player's entity, if player entities are enabled.
+    pub entity: Option<Entity>,
 }
 
 /// A resource that contains the players currently connected to the server.
@@ -57,6 +386,63 @@ pub struct RconPlayers {
 pub struct RconPlayer {
     pub unique_id: String,
     pub name: String,
+    /// Extra information like ping, team or score, shown as sortable columns in the player list
+    /// and included in the JSON API. Update it in `RconPlayers` as it changes.
+    #[serde(default)]
+    pub metadata: BTreeMap<String, RconValue>,
+    /// The address the player connected from, if the game provides it.
+    /// Used to refuse players from banned IP ranges and to remove them when their range is banned.
+    #[serde(default)]
+    #[reflect(ignore)]
+    pub ip: Option<IpAddr>,
+    /// An identifier of the player's device, if the game provides one. Used by `RconPlugin::detect_ban_evasion`.
+    #[serde(default)]
+    pub hardware_id: Option<String>,
+}
+
+impl RconPlayer {
+    pub fn new(unique_id: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            unique_id: unique_id.into(),
+            name: name.into(),
+            metadata: BTreeMap::new(),
+            ip: None,
+            hardware_id: None,
+        }
+    }
+
+    /// Sets the address the player connected from, e.g. `RconPlayer::new("steam_1", "Alice").with_ip(address.ip())`.
+    pub fn with_ip(mut self, ip: IpAddr) -> Self {
+        self.ip = Some(ip);
+        self
+    }
+
+    /// Sets an identifier of the player's device, e.g. a hash of their hardware.
+    pub fn with_hardware_id(mut self, hardware_id: impl Into<String>) -> Self {
+        self.hardware_id = Some(hardware_id.into());
+        self
+    }
+
+    /// The player as shown to an admin with the role. Like in the player list, the IP address
+    /// and hardware ID are only included for roles that can ban.
+    pub(crate) fn visible_to(mut self, role: RconRole) -> Self {
+        if !role.allows(RconPermission::Ban) {
+            self.ip = None;
+            self.hardware_id = None;
+        }
+        self
+    }
+
+    /// Adds a metadata value, e.g. `RconPlayer::new("steam_1", "Alice").with_metadata("ping", 42)`.
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<RconValue>) -> Self {
+        self.set_metadata(key, value);
+        self
+    }
+
+    /// Sets a metadata value, replacing any previous value with the same key.
+    pub fn set_metadata(&mut self, key: impl Into<String>, value: impl Into<RconValue>) {
+        self.metadata.insert(key.into(), value.into());
+    }
 }
 
 impl std::fmt::Display for RconPlayer {
@@ -65,6 +451,34 @@ impl std::fmt::Display for RconPlayer {
     }
 }
 
+fn refuse_banned_players_enabled(config: Res<RconConfig>) -> bool {
+    config.refuse_banned_players
+}
+
+/// The form submitted by the ban button in the player list.
+#[derive(Deserialize)]
+struct BanForm {
+    unique_id: String,
+    name: String,
+    /// One of the preset durations, `"custom"` to use `custom_duration`, or empty for a permanent ban.
+    #[serde(default)]
+    duration: String,
+    #[serde(default)]
+    custom_duration: String,
+    #[serde(default)]
+    reason: String,
+    #[serde(default)]
+    notes: String,
+}
+
+/// The form submitted by the kick button in the player list.
+#[derive(Deserialize)]
+struct KickForm {
+    unique_id: String,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
 /// A struct that represents a banned player. Contains information about the player at the time they were banned.
 #[derive(Component, Clone, Default, Serialize, Deserialize, PartialEq, Reflect)]
 pub struct DbRconBannedPlayer {
@@ -72,69 +486,182 @@ pub struct DbRconBannedPlayer {
     pub name: String,
 }
 
-async fn index() -> axum::response::Html<String> {
-    let markup = base_template(TemplateParams {
-        tab_title: "RCON Player Management".to_string(),
-        game_name: "Game Name".to_string(),
-        server_name: "Server Name".to_string(),
-        content: html! {
+async fn index(admin: axum::Extension<RconAdmin>) -> Result<axum::response::Html<String>, RconForbidden> {
+    admin.require(RconPermission::ViewPlayers)?;
+
+    Ok(render_page(Some(&admin), html! {
+        // The lists are fetched again whenever the server reports a change.
+        div data-rcon-events="/events" {
             h3 { "Connected Players" }
-            div id="player-list" hx-get="/players" hx-trigger="load" {}
+            // Replaced by the list, which then follows the live updates.
+            div id="player-list" hx-get="/players" hx-trigger="load" hx-swap="outerHTML" {}
             h3 { "Banned Players" }
-            div id="banned-player-list" hx-get="/ban_list" hx-trigger="load" {}
+            div id="banned-player-list" hx-get="/ban_list" hx-trigger="load, sse:bans, sse:ip_bans" {}
         }
-    });
-
-    axum::response::Html(markup.into_string())
+    }))
 }
 
-async fn list_players() -> axum::response::Html<String> {
+async fn list_players(
+    admin: axum::Extension<RconAdmin>,
+    sort: axum::extract::Query<PlayerSort>,
+) -> Result<axum::response::Html<String>, RconForbidden> {
+    admin.require(RconPermission::ViewPlayers)?;
+    let sort = sort.0;
+    sort.require(&admin)?;
+
     let players = AsyncWorld.resource::<RconPlayers>();    
-    let players = players.get_mut(|players| {
+    let mut players = players.get_mut(|players| {
         players.players.clone()
     }).unwrap();
+    sort.apply(&mut players);
+    let columns: BTreeSet<String> = players.iter().flat_map(|player| player.metadata.keys().cloned()).collect();
+    // Addresses are only shown to admins who can ban them.
+    let show_ip = admin.can(RconPermission::Ban) && players.iter().any(|player| player.ip.is_some());
+    let actions = AsyncWorld
+        .resource::<player_actions::RconPlayerActions>()
+        .cloned()
+        .unwrap_or_default();
+    let detect_ban_evasion = AsyncWorld.resource::<RconConfig>().get(|config| config.detect_ban_evasion).unwrap_or(false);
+    let links = match detect_ban_evasion {
+        true => {
+            let players = players.clone();
+            AsyncWorld.run(move |world: &mut World| evasion::find_links(world, &players))
+        }
+        false => Default::default(),
+    };
 
     let markup = html! {
-        div class="player-list" {
-            @for player in players {
-                (player_item(&player))
+        // Replaces itself, so the live updates keep the sort order. Bans change the ban evasion flags.
+        div id="player-list" hx-get={"/players?" (sort.query())} hx-trigger="sse:players, sse:bans" hx-swap="outerHTML" {
+            table class="player-list" {
+                thead {
+                    tr {
+                        (sort_header(&sort, "name", "Name"))
+                        (sort_header(&sort, "unique_id", "ID"))
+                        @if show_ip {
+                            (sort_header(&sort, "ip", "IP"))
+                        }
+                        @for column in &columns {
+                            (sort_header(&sort, column, column))
+                        }
+                        th {}
+                    }
+                }
+                tbody {
+                    @for player in &players {
+                        (player_item(player, &admin, show_ip, &columns, &actions, links.get(&player.unique_id)))
+                    }
+                }
             }
         }
     };
 
-    axum::response::Html(markup.into_string())
+    Ok(axum::response::Html(markup.into_string()))
 }
 
-/// A function that returns markup for a player item in the player list.
-fn player_item(player: &RconPlayer) -> Markup {
+/// A column header that sorts the player list by the column, or reverses the order if it is already sorted by it.
+fn sort_header(sort: &PlayerSort, column: &str, title: &str) -> Markup {
+    html! {
+        th {
+            button
+                type="button"
+                class="rcon-sort"
+                hx-get={"/players?" (sort.toggle(column))}
+                hx-target="#player-list"
+                hx-swap="outerHTML"
+            {
+                (title)
+                @if sort.sort == column {
+                    @if sort.desc { " ▼" } @else { " ▲" }
+                }
+            }
+        }
+    }
+}
+
+/// A function that returns markup for a player row in the player list, with a cell for each metadata column.
+/// Only shows the buttons for actions the admin is allowed to perform. Players linked to banned accounts are flagged.
+fn player_item(
+    player: &RconPlayer,
+    admin: &RconAdmin,
+    show_ip: bool,
+    columns: &BTreeSet<String>,
+    actions: &player_actions::RconPlayerActions,
+    links: Option<&Vec<RconEvasionLink>>,
+) -> Markup {
     let is_banned = AsyncWorld
         .query::<&DbRconBannedPlayer>()
         .get_mut(|mut query| {
-            query.iter().next()
-                .map(|banned| banned.unique_id == player.unique_id)
-                .unwrap_or(false)
+            query.iter().any(|banned| banned.unique_id == player.unique_id)
         })
         .unwrap_or(false);
 
     if is_banned {
-        warn!("You have added a banned player to the player list: {} (ID: {}). Omitting from list. But you should check if a player is banned before adding them to the player list.", player.name, player.unique_id);
+        warn!("You have added a banned player to the player list: {} (ID: {}). Omitting from list. Check RconBans::is_banned before adding players, or enable RconPlugin::refuse_banned_players.", player.name, player.unique_id);
     }
 
     html! {
         @if !is_banned {
-            div class="player-item" {
-                span { 
-                    (player.name) " (ID: " (player.unique_id) ")"
+            tr class="player-item" {
+                td {
+                    (player.name)
+                    @if let Some(links) = links {
+                        (evasion::evasion_flag(links))
+                    }
+                }
+                td { (player.unique_id) }
+                @if show_ip {
+                    td {
+                        @if let Some(ip) = player.ip {
+                            code { (ip) }
+                        }
+                    }
+                }
+                @for column in columns {
+                    td {
+                        @if let Some(value) = player.metadata.get(column) {
+                            (value)
+                        }
+                    }
                 }
+                td {
+                    div class="player-actions" {
+                        @if admin.can(RconPermission::Kick) {
+                            form
+                                hx-post="/kick_player"
+                                hx-target="body"
+                                hx-swap="innerHTML"
+                            {
+                                input type="hidden" name="unique_id" value=(player.unique_id);
+                                input type="text" name="reason" placeholder="Reason (optional)";
+                                button type="submit" { "Kick" }
+                            }
+                        }
 
-                form 
-                    hx-post="/ban_player"
-                    hx-target="body"
-                    hx-swap="innerHTML"
-                {
-                    input type="hidden" name="unique_id" value=(player.unique_id);
-                    input type="hidden" name="name" value=(player.name);
-                    button type="submit" { "Ban" }
+                        @if admin.can(RconPermission::Ban) {
+                            form
+                                hx-post="/ban_player"
+                                hx-target="body"
+                                hx-swap="innerHTML"
+                            {
+                                input type="hidden" name="unique_id" value=(player.unique_id);
+                                input type="hidden" name="name" value=(player.name);
+                                select name="duration" {
+                                    option value="" { "Permanent" }
+                                    option value="1h" { "1 hour" }
+                                    option value="1d" { "1 day" }
+                                    option value="7d" { "7 days" }
+                                    option value="custom" { "Custom" }
+                                }
+                                input type="text" name="custom_duration" placeholder="Custom, e.g. 30m, 12h, 2w";
+                                input type="text" name="reason" placeholder="Reason";
+                                input type="text" name="notes" placeholder="Notes (optional)";
+                                button type="submit" { "Ban" }
+                            }
+                        }
+
+                        (player_actions::action_forms(player, admin, actions))
+                    }
                 }
             }
         }
@@ -142,77 +669,131 @@ fn player_item(player: &RconPlayer) -> Markup {
 }
 
 /// Lists all banned players (database query).
-async fn list_bans() -> axum::response::Html<String> {
-    let banned_players = AsyncWorld.query::<&DbRconBannedPlayer>();
-    let banned_players = banned_players.get_mut(|mut query| -> Vec<DbRconBannedPlayer> {
+async fn list_bans(admin: axum::Extension<RconAdmin>) -> Result<axum::response::Html<String>, RconForbidden> {
+    admin.require(RconPermission::ViewPlayers)?;
+
+    let banned_players = AsyncWorld.query::<(
+        &DbRconBannedPlayer,
+        Option<&DbRconBanExpiry>,
+        Option<&DbRconBanDetails>,
+    )>();
+    let banned_players = banned_players.get_mut(|mut query| {
         let mut players = vec![];
-        for player in query.iter() {
-            players.push(player.clone());
+        for (player, expiry, details) in query.iter() {
+            players.push((player.clone(), expiry.cloned(), details.cloned()));
         }
         players
     }).unwrap_or_default();
 
     let markup = html! {
         div class="ban-list" {
-            @for player in banned_players {
+            @for (player, expiry, details) in banned_players {
                 div class="banned-player" {
                     span { (player.name) " (ID: " (player.unique_id) ")" }
-                    button
-                        hx-post={"/unban_player/" (player.unique_id)}
-                        hx-target="body"
-                        hx-swap="innerHTML"
-                        { "Unban" }
+                    @match expiry {
+                        Some(expiry) => span class="ban-expiry" { "Expires in " (bans::format_duration(expiry.remaining())) },
+                        None => span class="ban-expiry" { "Permanent" },
+                    }
+                    @if let Some(details) = details {
+                        span class="ban-details" {
+                            "Banned by " (details.issued_by) " on " (audit::format_timestamp(details.created_at))
+                        }
+                        @if let Some(reason) = details.reason {
+                            span class="ban-reason" { "Reason: " (reason) }
+                        }
+                        @if let Some(notes) = details.notes {
+                            span class="ban-notes" { "Notes: " (notes) }
+                        }
+                    }
+                    @if admin.can(RconPermission::Unban) {
+                        button
+                            hx-post={"/unban_player/" (player.unique_id)}
+                            hx-target="body"
+                            hx-swap="innerHTML"
+                            { "Unban" }
+                    }
                 }
             }
         }
+        (ip_bans::ip_ban_list(&admin))
     };
 
-    axum::response::Html(markup.into_string())
+    Ok(axum::response::Html(markup.into_string()))
 }
 
 /// Adds a player to the banned list (database update).
-/// Also removes the player from the player list.
+/// Also removes the player from the player list and sends a `RconPlayerBanned` event.
 async fn ban_player(
-    form: axum::extract::Form<RconPlayer>,
-) -> axum::response::Html<String> {
-    let id = form.unique_id.clone();
-    let name = form.name.clone();
+    admin: axum::Extension<RconAdmin>,
+    actor: RconActor,
+    form: axum::extract::Form<BanForm>,
+) -> Result<axum::response::Html<String>, RconForbidden> {
+    admin.require(RconPermission::Ban)?;
+    let BanForm { unique_id, name, duration, custom_duration, reason, notes } = form.0;
+    let player = RconPlayer::new(unique_id, name);
+    let non_empty = |value: String| Some(value.trim().to_string()).filter(|value| !value.is_empty());
 
-    if id.is_empty() || name.is_empty() {
-        warn!("Invalid player data: ID: {}, Name: {}", id, name);
-        return index().await;
-    }
+    let duration = match duration.as_str() {
+        "" => None,
+        "custom" => Some(custom_duration.as_str()),
+        preset => Some(preset),
+    };
+    let duration = match duration {
+        Some(input) => match parse_duration(input) {
+            Some(duration) => Some(duration),
+            None => {
+                warn!("Invalid ban duration: {}", input);
+                return index(admin).await;
+            }
+        },
+        None => None,
+    };
 
-    AsyncWorld.spawn_bundle(DbRconBannedPlayer {
-        unique_id: id.clone(),
-        name: name,
-    });
+    let options = RconBanOptions {
+        duration,
+        reason: non_empty(reason),
+        notes: non_empty(notes),
+    };
 
-    // remove the player from the player list
-    if let Err(e) = AsyncWorld.resource::<RconPlayers>().get_mut(|players| {
-        players.players.retain(|player| player.unique_id != id);
+    if let Err(e) = AsyncWorld.run(move |world: &mut World| {
+        actions::ban_player(world, &actor, player, options)
     }) {
-        error!("Failed to remove player from player list: {}", e);
+        warn!("Failed to ban player: {}", e);
     }
 
-    index().await
+    index(admin).await
+}
+
+/// Removes a player from the player list and notifies the plugin user with a `RconPlayerKicked` event.
+async fn kick_player(
+    admin: axum::Extension<RconAdmin>,
+    actor: RconActor,
+    form: axum::extract::Form<KickForm>,
+) -> Result<axum::response::Html<String>, RconForbidden> {
+    admin.require(RconPermission::Kick)?;
+    let id = form.unique_id.clone();
+    let reason = form.reason.clone().filter(|reason| !reason.trim().is_empty());
+
+    if let Err(e) = AsyncWorld.run(move |world: &mut World| actions::kick_player(world, &actor, &id, reason)) {
+        warn!("Failed to kick player: {}", e);
+    }
+
+    index(admin).await
 }
 
 /// Removes a player from the banned list (database update).
+/// Also sends a `RconPlayerUnbanned` event.
 async fn unban_player(
+    admin: axum::Extension<RconAdmin>,
+    actor: RconActor,
     path: axum::extract::Path<String>,
-) -> axum::response::Html<String> {
-    
-    AsyncWorld.apply_command(move |world: &mut World| {
-        let id = path.0.clone();
-
-        let mut banned_players = world.query::<(Entity, &mut DbRconBannedPlayer)>();
-        for (entity, banned) in banned_players.iter_mut(world) {
-            if banned.unique_id == id {
-                world.despawn(entity);
-                break;
-            }
-        }
-    });
-    index().await
+) -> Result<axum::response::Html<String>, RconForbidden> {
+    admin.require(RconPermission::Unban)?;
+    let id = path.0;
+
+    if let Err(e) = AsyncWorld.run(move |world: &mut World| actions::unban_player(world, &actor, &id)) {
+        warn!("Failed to unban player: {}", e);
+    }
+
+    index(admin).await
 }
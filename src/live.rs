@@ -0,0 +1,248 @@
+//! Live updates for open dashboards and API clients, sent as Server-Sent Events.
+//! Systems watch `RconPlayers` and the bans, and send the new state to every connected client.
+
+use std::{convert::Infallible, time::Duration};
+
+use axum::response::sse::{Event, Sse};
+use bevy::{
+    ecs::system::SystemState,
+    prelude::*,
+    tasks::futures_lite::{Stream, StreamExt},
+};
+use bevy_defer::AsyncWorld;
+
+use crate::{
+    auth::RconCredential, DbRconBanDetails, DbRconBanExpiry, DbRconBannedPlayer, DbRconIpBan, RconAdmin, RconBanInfo, RconForbidden,
+    RconPermission, RconPlayer, RconPlayers, RconRole,
+};
+
+/// How many updates are queued for a client that is not reading. Further updates are dropped,
+/// which is fine because every update contains the full state.
+const CLIENT_QUEUE_SIZE: usize = 16;
+
+/// The least time between two player list updates, so that metadata the game changes every frame,
+/// like the ping, does not flood the clients. Changes within the interval are sent together at its end.
+const PLAYERS_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// An update sent to the clients.
+#[derive(Clone)]
+pub(crate) enum LiveUpdate {
+    /// An event with its name and JSON data.
+    Event { name: &'static str, data: String },
+    /// A comment that keeps idle connections open.
+    KeepAlive,
+}
+
+/// A resource that contains the connected SSE clients.
+#[derive(Resource, Default)]
+pub(crate) struct RconLiveClients {
+    clients: Vec<LiveClient>,
+}
+
+struct LiveClient {
+    sender: async_channel::Sender<LiveUpdate>,
+    /// Checked before every update, see `check_live_clients`.
+    credential: RconCredential,
+    /// The current role of the admin, which decides whether player addresses are sent.
+    role: RconRole,
+}
+
+impl RconLiveClients {
+    /// Sends an update to every client, dropping the clients that disconnected.
+    fn broadcast(&mut self, update: LiveUpdate) {
+        self.broadcast_by_role(|_| Some(update.clone()));
+    }
+
+    /// Sends every client the update for its admin's role, dropping the clients that disconnected.
+    fn broadcast_by_role(&mut self, update: impl Fn(RconRole) -> Option<LiveUpdate>) {
+        self.clients.retain(|client| {
+            let Some(update) = update(client.role) else {
+                return true;
+            };
+            match client.sender.try_send(update) {
+                Ok(()) | Err(async_channel::TrySendError::Full(_)) => true,
+                Err(async_channel::TrySendError::Closed(_)) => false,
+            }
+        });
+    }
+}
+
+/// Drops the clients whose session ended or whose token was revoked, which ends their event stream,
+/// and updates the role of the others. Runs before the updates are sent.
+pub(crate) fn check_live_clients(world: &mut World) {
+    if world.resource::<RconLiveClients>().clients.is_empty() {
+        return;
+    }
+
+    let mut clients = std::mem::take(&mut world.resource_mut::<RconLiveClients>().clients);
+    clients.retain_mut(|client| match client.credential.admin(world) {
+        Some(admin) => {
+            client.role = admin.role;
+            true
+        }
+        None => false,
+    });
+    // Clients that connected meanwhile are kept.
+    world.resource_mut::<RconLiveClients>().clients.extend(clients);
+}
+
+type BanQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static DbRconBannedPlayer,
+        Option<&'static DbRconBanExpiry>,
+        Option<&'static DbRconBanDetails>,
+    ),
+>;
+
+/// The players as an admin with the role may see them, see `RconPlayer::visible_to`.
+fn players_update(players: &RconPlayers, role: RconRole) -> Option<LiveUpdate> {
+    let players: Vec<RconPlayer> = players.players.iter().map(|player| player.clone().visible_to(role)).collect();
+    match serde_json::to_string(&players) {
+        Ok(data) => Some(LiveUpdate::Event { name: "players", data }),
+        Err(e) => {
+            error!("Failed to serialize players: {}", e);
+            None
+        }
+    }
+}
+
+fn bans_update(bans: &BanQuery) -> Option<LiveUpdate> {
+    let bans: Vec<RconBanInfo> = bans
+        .iter()
+        .map(|(banned, expiry, details)| RconBanInfo {
+            banned: banned.clone(),
+            expiry: expiry.cloned(),
+            details: details.cloned(),
+        })
+        .collect();
+
+    match serde_json::to_string(&bans) {
+        Ok(data) => Some(LiveUpdate::Event { name: "bans", data }),
+        Err(e) => {
+            error!("Failed to serialize bans: {}", e);
+            None
+        }
+    }
+}
+
+fn ip_bans_update(ip_bans: &Query<&DbRconIpBan>) -> Option<LiveUpdate> {
+    let ip_bans: Vec<&DbRconIpBan> = ip_bans.iter().collect();
+    match serde_json::to_string(&ip_bans) {
+        Ok(data) => Some(LiveUpdate::Event { name: "ip_bans", data }),
+        Err(e) => {
+            error!("Failed to serialize IP bans: {}", e);
+            None
+        }
+    }
+}
+
+/// Whether the players changed since the last update, and when it was sent.
+#[derive(Default)]
+pub(crate) struct PlayersThrottle {
+    pending: bool,
+    sent_at: Option<Duration>,
+}
+
+/// Sends the players when `RconPlayers` changes, and the bans when a ban is added or removed.
+pub(crate) fn broadcast_changes(
+    players: Res<RconPlayers>,
+    time: Res<Time<Real>>,
+    mut throttle: Local<PlayersThrottle>,
+    added_bans: Query<(), Added<DbRconBannedPlayer>>,
+    mut removed_bans: RemovedComponents<DbRconBannedPlayer>,
+    bans: BanQuery,
+    mut clients: ResMut<RconLiveClients>,
+) {
+    // Always read the removed bans, so they are not reported late once a client connects.
+    let bans_removed = removed_bans.read().count() > 0;
+    if clients.clients.is_empty() {
+        return;
+    }
+
+    throttle.pending |= players.is_changed();
+    let interval_passed = throttle
+        .sent_at
+        .is_none_or(|sent_at| time.elapsed() - sent_at >= PLAYERS_UPDATE_INTERVAL);
+    if throttle.pending && interval_passed {
+        // Serialized once per role, not once per client.
+        let updates = RconRole::ALL.map(|role| (role, players_update(&players, role)));
+        clients.broadcast_by_role(|role| {
+            updates
+                .iter()
+                .find(|(update_role, _)| *update_role == role)
+                .and_then(|(_, update)| update.clone())
+        });
+        throttle.pending = false;
+        throttle.sent_at = Some(time.elapsed());
+    }
+
+    if bans_removed || !added_bans.is_empty() {
+        if let Some(update) = bans_update(&bans) {
+            clients.broadcast(update);
+        }
+    }
+}
+
+/// Sends the IP bans when one is added or removed.
+pub(crate) fn broadcast_ip_ban_changes(
+    added_ip_bans: Query<(), Added<DbRconIpBan>>,
+    mut removed_ip_bans: RemovedComponents<DbRconIpBan>,
+    ip_bans: Query<&DbRconIpBan>,
+    mut clients: ResMut<RconLiveClients>,
+) {
+    // Always read the removed bans, so they are not reported late once a client connects.
+    let ip_bans_removed = removed_ip_bans.read().count() > 0;
+    if clients.clients.is_empty() || (!ip_bans_removed && added_ip_bans.is_empty()) {
+        return;
+    }
+
+    // Only admins who can ban see addresses.
+    if let Some(update) = ip_bans_update(&ip_bans) {
+        clients.broadcast_by_role(|role| role.allows(RconPermission::Ban).then(|| update.clone()));
+    }
+}
+
+/// Sends a comment to every client now and then, so proxies do not close idle connections.
+pub(crate) fn keep_alive(mut clients: ResMut<RconLiveClients>) {
+    clients.broadcast(LiveUpdate::KeepAlive);
+}
+
+/// The event stream. Sends `players`, `bans` and `ip_bans` events with the full lists as JSON data,
+/// starting with the current state. Player addresses and `ip_bans` are only sent to admins who can ban.
+pub(crate) async fn events(
+    admin: axum::Extension<RconAdmin>,
+    credential: axum::Extension<RconCredential>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, RconForbidden> {
+    admin.require(RconPermission::ViewPlayers)?;
+
+    let (sender, receiver) = async_channel::bounded(CLIENT_QUEUE_SIZE);
+    AsyncWorld.run(move |world: &mut World| {
+        let mut bans = SystemState::<(BanQuery, Query<&DbRconIpBan>)>::new(world);
+        let (bans, ip_bans) = bans.get(world);
+        let initial = [
+            players_update(world.resource::<RconPlayers>(), admin.role),
+            bans_update(&bans),
+            ip_bans_update(&ip_bans).filter(|_| admin.can(RconPermission::Ban)),
+        ];
+        for update in initial.into_iter().flatten() {
+            let _ = sender.try_send(update);
+        }
+        let client = LiveClient {
+            sender,
+            credential: credential.0,
+            role: admin.role,
+        };
+        world.resource_mut::<RconLiveClients>().clients.push(client);
+    });
+
+    let stream = receiver.map(|update| {
+        Ok(match update {
+            LiveUpdate::Event { name, data } => Event::default().event(name).data(data),
+            LiveUpdate::KeepAlive => Event::default().comment("keep-alive"),
+        })
+    });
+
+    Ok(Sse::new(stream))
+}
@@ -0,0 +1,292 @@
+//! Captures the server logs into a ring buffer, so admins can read them in the panel.
+//! The capture is a `tracing` layer, enabled by passing `rcon_log_layer` to `LogPlugin::custom_layer`.
+
+use std::{
+    collections::VecDeque,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use axum::Json;
+use bevy::{
+    log::{
+        tracing_subscriber::{layer::Context, Layer},
+        BoxedLayer, Level,
+    },
+    prelude::*,
+    utils::tracing::{field::Field, field::Visit, Subscriber},
+};
+use bevy_defer::{AsyncAccess, AsyncWorld};
+use maud::{html, Markup};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::ApiError,
+    audit::format_timestamp,
+    audit::unix_now,
+    template::render_page,
+    RconAdmin, RconForbidden, RconPermission,
+};
+
+/// How many log entries are kept. Older entries are dropped.
+const LOG_CAPACITY: usize = 1000;
+
+/// How many entries the logs page shows at first.
+const LOG_PAGE_SIZE: usize = 200;
+
+/// A captured log event.
+#[derive(Clone, Serialize)]
+pub struct RconLogEntry {
+    /// Increases with every entry, used to fetch only newer entries.
+    pub id: u64,
+    /// Seconds since the unix epoch.
+    pub timestamp: u64,
+    /// `ERROR`, `WARN`, `INFO`, `DEBUG` or `TRACE`.
+    pub level: String,
+    /// The module the event was logged from, e.g. `bevy_rcon::bans`.
+    pub target: String,
+    pub message: String,
+}
+
+#[derive(Default)]
+struct LogBuffer {
+    entries: VecDeque<RconLogEntry>,
+    /// The ID of the newest entry, IDs start at 1.
+    last_id: u64,
+}
+
+/// A resource that contains the captured logs.
+/// It is empty unless `rcon_log_layer` is passed to `LogPlugin::custom_layer`.
+#[derive(Resource, Clone, Default)]
+pub struct RconLogs {
+    buffer: Arc<Mutex<LogBuffer>>,
+    capturing: bool,
+}
+
+impl RconLogs {
+    /// Whether the logs are being captured.
+    pub fn is_capturing(&self) -> bool {
+        self.capturing
+    }
+
+    /// The captured entries that match the filter, oldest first.
+    /// At most `limit` entries are returned, the newest ones if there are more.
+    pub fn entries(&self, filter: &RconLogFilter, limit: usize) -> Vec<RconLogEntry> {
+        let Ok(buffer) = self.buffer.lock() else {
+            return vec![];
+        };
+        let mut entries: Vec<RconLogEntry> = buffer
+            .entries
+            .iter()
+            .rev()
+            .filter(|entry| filter.matches(entry))
+            .take(limit)
+            .cloned()
+            .collect();
+        entries.reverse();
+        entries
+    }
+
+    /// The ID of the newest entry, 0 if there are none.
+    fn last_id(&self) -> u64 {
+        self.buffer.lock().map(|buffer| buffer.last_id).unwrap_or_default()
+    }
+}
+
+/// Filters for the captured logs, used as query parameters by the logs page and the JSON endpoint.
+#[derive(Deserialize, Default, Clone)]
+pub struct RconLogFilter {
+    /// Only entries with a higher ID.
+    #[serde(default)]
+    pub after: Option<u64>,
+    /// The least severe level to include, e.g. `warn` for warnings and errors. Empty for all levels.
+    #[serde(default)]
+    pub level: String,
+    /// Only entries whose target contains this text.
+    #[serde(default)]
+    pub target: String,
+}
+
+impl RconLogFilter {
+    fn matches(&self, entry: &RconLogEntry) -> bool {
+        // More verbose levels compare as greater.
+        let level_matches = match (Level::from_str(&self.level), Level::from_str(&entry.level)) {
+            (Ok(min_level), Ok(level)) => level <= min_level,
+            _ => true,
+        };
+
+        self.after.is_none_or(|after| entry.id > after)
+            && level_matches
+            && (self.target.is_empty() || entry.target.contains(&self.target))
+    }
+
+    /// The filter as query parameters, for the polling requests.
+    fn query(&self, after: u64) -> String {
+        format!("after={}&level={}&target={}", after, encode(&self.level), encode(&self.target))
+    }
+}
+
+/// Percent-encodes a query parameter value.
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b':' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// The `tracing` layer that writes events into the buffer.
+struct RconLogLayer {
+    buffer: Arc<Mutex<LogBuffer>>,
+}
+
+impl<S: Subscriber> Layer<S> for RconLogLayer {
+    fn on_event(&self, event: &bevy::utils::tracing::Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        let Ok(mut buffer) = self.buffer.lock() else {
+            return;
+        };
+        buffer.last_id += 1;
+        let id = buffer.last_id;
+        buffer.entries.push_back(RconLogEntry {
+            id,
+            timestamp: unix_now(),
+            level: event.metadata().level().to_string(),
+            target: event.metadata().target().to_string(),
+            message: visitor.message,
+        });
+        if buffer.entries.len() > LOG_CAPACITY {
+            buffer.entries.pop_front();
+        }
+    }
+}
+
+/// Formats the message of an event, followed by its other fields as `name=value`.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message.insert_str(0, &format!("{:?}", value));
+        } else {
+            self.message.push_str(&format!(" {}={:?}", field.name(), value));
+        }
+    }
+}
+
+/// Captures the logs for the panel. Pass it to `LogPlugin`:
+///
+/// ```ignore
+/// app.add_plugins(DefaultPlugins.set(LogPlugin {
+///     custom_layer: bevy_rcon::rcon_log_layer,
+///     ..default()
+/// }));
+/// ```
+pub fn rcon_log_layer(app: &mut App) -> Option<BoxedLayer> {
+    let logs = RconLogs {
+        buffer: Arc::default(),
+        capturing: true,
+    };
+    let layer = RconLogLayer {
+        buffer: logs.buffer.clone(),
+    };
+    app.insert_resource(logs);
+    Some(Box::new(layer))
+}
+
+/// The logs page, with the filters and the newest entries. New entries are fetched every few seconds.
+pub(crate) async fn logs_page(
+    admin: axum::Extension<RconAdmin>,
+    filter: axum::extract::Query<RconLogFilter>,
+) -> Result<axum::response::Html<String>, RconForbidden> {
+    admin.require(RconPermission::ViewLogs)?;
+    let filter = filter.0;
+
+    let logs = AsyncWorld.resource::<RconLogs>().cloned().unwrap_or_default();
+    let entries = logs.entries(&filter, LOG_PAGE_SIZE);
+    let content = html! {
+        h3 { "Server Logs" }
+        @if !logs.is_capturing() {
+            p class="error" {
+                "Logs are not being captured. Set " code { "custom_layer: bevy_rcon::rcon_log_layer" }
+                " on the " code { "LogPlugin" } " to capture them."
+            }
+        }
+        form method="get" action="/logs" {
+            select name="level" {
+                @for (value, label) in [("", "All levels"), ("error", "Errors"), ("warn", "Warnings and up"), ("info", "Info and up"), ("debug", "Debug and up")] {
+                    option value=(value) selected[filter.level == value] { (label) }
+                }
+            }
+            input type="text" name="target" placeholder="Target, e.g. bevy_rcon" value=(filter.target);
+            button type="submit" { "Filter" }
+        }
+        div class="log-entries" {
+            @for entry in &entries {
+                (log_entry(entry))
+            }
+            (log_poller(&filter, logs.last_id()))
+        }
+    };
+
+    Ok(render_page(Some(&admin), content))
+}
+
+/// The entries after the poller's ID, followed by a new poller that replaces the old one.
+pub(crate) async fn logs_tail(
+    admin: axum::Extension<RconAdmin>,
+    filter: axum::extract::Query<RconLogFilter>,
+) -> Result<axum::response::Html<String>, RconForbidden> {
+    admin.require(RconPermission::ViewLogs)?;
+    let filter = filter.0;
+
+    let logs = AsyncWorld.resource::<RconLogs>().cloned().unwrap_or_default();
+    let entries = logs.entries(&filter, LOG_CAPACITY);
+    let last_id = entries.last().map(|entry| entry.id).or(filter.after).unwrap_or_default();
+
+    let markup = html! {
+        @for entry in &entries {
+            (log_entry(entry))
+        }
+        (log_poller(&filter, last_id))
+    };
+
+    Ok(axum::response::Html(markup.into_string()))
+}
+
+/// `GET /api/v1/logs`, takes the same filters as the logs page.
+pub(crate) async fn logs_json(
+    admin: axum::Extension<RconAdmin>,
+    filter: Result<axum::extract::Query<RconLogFilter>, axum::extract::rejection::QueryRejection>,
+) -> Result<Json<Vec<RconLogEntry>>, ApiError> {
+    admin.require(RconPermission::ViewLogs)?;
+    let filter = filter?;
+
+    let logs = AsyncWorld.resource::<RconLogs>().cloned().unwrap_or_default();
+    Ok(Json(logs.entries(&filter, LOG_CAPACITY)))
+}
+
+fn log_entry(entry: &RconLogEntry) -> Markup {
+    html! {
+        div class={"log-entry log-" (entry.level.to_lowercase())} {
+            span class="log-time" { (format_timestamp(entry.timestamp)) }
+            span class="log-level" { (entry.level) }
+            span class="log-target" { (entry.target) }
+            span class="log-message" { (entry.message) }
+        }
+    }
+}
+
+/// Polls for entries after `after`, and is replaced by them.
+fn log_poller(filter: &RconLogFilter, after: u64) -> Markup {
+    html! {
+        div hx-get={"/logs/tail?" (filter.query(after))} hx-trigger="every 2s" hx-swap="outerHTML" {}
+    }
+}
@@ -0,0 +1,224 @@
+//! Extra information about players, like ping, team or score, shown as columns in the player list.
+
+use std::{cmp::Ordering, time::Duration};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{bans::format_duration, RconAdmin, RconForbidden, RconPermission, RconPlayer};
+
+/// A value in `RconPlayer::metadata`. Numbers and durations sort numerically, text sorts alphabetically.
+/// Serialized as a plain JSON value, durations as seconds. Because the JSON does not say which kind a value is,
+/// a serialized duration deserializes as `Integer`, and an integer that does not fit in `i64` as `Float`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Reflect)]
+#[serde(untagged)]
+pub enum RconValue {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    /// Shown like `1h 30m`, e.g. for the session length.
+    Duration(u64),
+    Text(String),
+}
+
+impl RconValue {
+    /// Compares values for sorting. Values of different kinds are ordered by kind.
+    pub(crate) fn compare(&self, other: &RconValue) -> Ordering {
+        match (self.as_number(), other.as_number()) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            _ => match (self, other) {
+                (RconValue::Bool(a), RconValue::Bool(b)) => a.cmp(b),
+                (RconValue::Text(a), RconValue::Text(b)) => a.to_lowercase().cmp(&b.to_lowercase()),
+                _ => self.rank().cmp(&other.rank()),
+            },
+        }
+    }
+
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            RconValue::Integer(value) => Some(*value as f64),
+            RconValue::Float(value) => Some(*value),
+            RconValue::Duration(seconds) => Some(*seconds as f64),
+            RconValue::Bool(_) | RconValue::Text(_) => None,
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            RconValue::Bool(_) => 0,
+            RconValue::Integer(_) | RconValue::Float(_) | RconValue::Duration(_) => 1,
+            RconValue::Text(_) => 2,
+        }
+    }
+}
+
+impl std::fmt::Display for RconValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RconValue::Bool(value) => write!(f, "{}", if *value { "Yes" } else { "No" }),
+            RconValue::Integer(value) => write!(f, "{}", value),
+            RconValue::Float(value) => write!(f, "{:.2}", value),
+            RconValue::Duration(seconds) => write!(f, "{}", format_duration(Duration::from_secs(*seconds))),
+            RconValue::Text(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl From<bool> for RconValue {
+    fn from(value: bool) -> Self {
+        RconValue::Bool(value)
+    }
+}
+
+macro_rules! impl_from_integer {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for RconValue {
+                fn from(value: $ty) -> Self {
+                    RconValue::Integer(i64::from(value))
+                }
+            }
+        )*
+    };
+}
+
+impl_from_integer!(i8, i16, i32, i64, u8, u16, u32);
+
+/// Unsigned types that can hold values above `i64::MAX`, which are saturated to `i64::MAX`.
+macro_rules! impl_from_unsigned {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for RconValue {
+                fn from(value: $ty) -> Self {
+                    RconValue::Integer(i64::try_from(value).unwrap_or(i64::MAX))
+                }
+            }
+        )*
+    };
+}
+
+impl_from_unsigned!(u64, usize);
+
+impl From<f32> for RconValue {
+    fn from(value: f32) -> Self {
+        RconValue::Float(value as f64)
+    }
+}
+
+impl From<f64> for RconValue {
+    fn from(value: f64) -> Self {
+        RconValue::Float(value)
+    }
+}
+
+impl From<Duration> for RconValue {
+    fn from(value: Duration) -> Self {
+        RconValue::Duration(value.as_secs())
+    }
+}
+
+impl From<&str> for RconValue {
+    fn from(value: &str) -> Self {
+        RconValue::Text(value.to_string())
+    }
+}
+
+impl From<String> for RconValue {
+    fn from(value: String) -> Self {
+        RconValue::Text(value)
+    }
+}
+
+/// How to sort the player list, used as query parameters by the panel and the JSON API.
+#[derive(Deserialize, Default, Clone)]
+pub(crate) struct PlayerSort {
+    /// `name`, `unique_id`, `ip` or a metadata key. Empty keeps the order of `RconPlayers`.
+    #[serde(default)]
+    pub(crate) sort: String,
+    #[serde(default)]
+    pub(crate) desc: bool,
+}
+
+impl PlayerSort {
+    /// Returns an error response if the admin may not sort by the column.
+    /// Only admins who can ban see IP addresses, and the order would reveal them to the others.
+    pub(crate) fn require(&self, admin: &RconAdmin) -> Result<(), RconForbidden> {
+        match self.sort.as_str() {
+            "ip" => admin.require(RconPermission::Ban),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sorts the players in place. Players without the metadata key or IP come last in both directions.
+    pub(crate) fn apply(&self, players: &mut [RconPlayer]) {
+        let order = |ordering: Ordering| if self.desc { ordering.reverse() } else { ordering };
+        match self.sort.as_str() {
+            "" => {}
+            "name" => players.sort_by(|a, b| order(a.name.to_lowercase().cmp(&b.name.to_lowercase()))),
+            "unique_id" => players.sort_by(|a, b| order(a.unique_id.cmp(&b.unique_id))),
+            "ip" => players.sort_by(|a, b| match (a.ip, b.ip) {
+                (Some(a), Some(b)) => order(a.cmp(&b)),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }),
+            key => players.sort_by(|a, b| match (a.metadata.get(key), b.metadata.get(key)) {
+                (Some(a), Some(b)) => order(a.compare(b)),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }),
+        }
+    }
+
+    /// The query parameters of this sort order.
+    pub(crate) fn query(&self) -> String {
+        serde_urlencoded::to_string([("sort", self.sort.as_str()), ("desc", if self.desc { "true" } else { "false" })])
+            .unwrap_or_default()
+    }
+
+    /// The query parameters that sort by `column`, in the opposite direction if it is already sorted by it.
+    pub(crate) fn toggle(&self, column: &str) -> String {
+        PlayerSort {
+            sort: column.to_string(),
+            desc: self.sort == column && !self.desc,
+        }
+        .query()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saturates_unsigned_integers_above_i64_max() {
+        assert_eq!(RconValue::from(42u64), RconValue::Integer(42));
+        assert_eq!(RconValue::from(u64::MAX), RconValue::Integer(i64::MAX));
+        assert_eq!(RconValue::from(usize::MAX), RconValue::Integer(i64::MAX));
+        assert_eq!(RconValue::from(u32::MAX), RconValue::Integer(i64::from(u32::MAX)));
+        assert_eq!(RconValue::from(-5i8), RconValue::Integer(-5));
+    }
+
+    #[test]
+    fn serializes_durations_as_seconds_that_deserialize_as_integers() {
+        let json = serde_json::to_string(&RconValue::from(Duration::from_secs(90))).unwrap();
+        assert_eq!(json, "90");
+        assert_eq!(serde_json::from_str::<RconValue>(&json).unwrap(), RconValue::Integer(90));
+    }
+
+    #[test]
+    fn sorts_numbers_of_any_kind_together() {
+        let mut values = vec![
+            RconValue::from("text"),
+            RconValue::from(2.5),
+            RconValue::from(3u8),
+            RconValue::from(true),
+        ];
+        values.sort_by(RconValue::compare);
+        assert_eq!(
+            values,
+            vec![RconValue::from(true), RconValue::from(2.5), RconValue::from(3u8), RconValue::from("text")]
+        );
+    }
+}
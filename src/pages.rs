@@ -0,0 +1,98 @@
+//! Pages added by the game, rendered in the same layout and navigation as the built in pages.
+//! Register them with `RconAppExt::add_rcon_page`.
+
+use std::{collections::HashMap, future::Future, sync::Arc};
+
+use axum::{extract::Form, Extension};
+use bevy::prelude::*;
+use bevy_webserver::RouterAppExt;
+use maud::Markup;
+
+use crate::{template::render_page, RconAdmin, RconForbidden, RconPermission};
+
+/// The description of a custom page, built from its title with `RconPage::new` or from a `&str`.
+#[derive(Clone)]
+pub struct RconPage {
+    /// The title shown in the navigation.
+    pub title: String,
+    /// The permission needed to open the page. Defaults to `RconPermission::ViewPlayers`.
+    pub permission: RconPermission,
+}
+
+impl RconPage {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            permission: RconPermission::ViewPlayers,
+        }
+    }
+
+    pub fn permission(mut self, permission: RconPermission) -> Self {
+        self.permission = permission;
+        self
+    }
+}
+
+impl From<&str> for RconPage {
+    fn from(title: &str) -> Self {
+        Self::new(title)
+    }
+}
+
+impl From<String> for RconPage {
+    fn from(title: String) -> Self {
+        Self::new(title)
+    }
+}
+
+/// What a custom page handler receives.
+pub struct RconPageRequest {
+    /// The admin who opened the page, already checked for the page's permission.
+    pub admin: RconAdmin,
+    /// The query parameters of a `GET`, or the form fields of a `POST`.
+    pub params: HashMap<String, String>,
+}
+
+/// A registered custom page.
+#[derive(Clone)]
+pub(crate) struct RegisteredPage {
+    pub(crate) path: String,
+    pub(crate) page: RconPage,
+}
+
+/// A resource that contains the custom pages, in the order they were added.
+#[derive(Resource, Clone, Default)]
+pub(crate) struct RconPages {
+    pub(crate) pages: Vec<RegisteredPage>,
+}
+
+/// Adds the route of a custom page, and lists it for the navigation.
+pub(crate) fn add_page<F, Fut>(app: &mut App, path: &str, page: RconPage, handler: F)
+where
+    F: Fn(RconPageRequest) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Markup> + Send + 'static,
+{
+    let handler = Arc::new(handler);
+    let permission = page.permission;
+    let route = move |admin: Extension<RconAdmin>, params: Form<HashMap<String, String>>| {
+        let handler = handler.clone();
+        async move {
+            admin.require(permission)?;
+            let content = handler(RconPageRequest {
+                admin: admin.0.clone(),
+                params: params.0,
+            })
+            .await;
+            Ok::<_, RconForbidden>(render_page(Some(&admin), content))
+        }
+    };
+
+    app.world_mut()
+        .get_resource_or_init::<RconPages>()
+        .pages
+        .push(RegisteredPage {
+            path: path.to_string(),
+            page,
+        });
+    app.route(path, axum::routing::get(route.clone()).post(route));
+}
@@ -0,0 +1,221 @@
+//! Moderation verbs added by the game, like mute or teleport to spawn.
+//! Each action is a button in the player list, optionally with form fields, that sends a typed event.
+//! Register them with `RconAppExt::add_rcon_player_action`.
+
+use axum::{
+    extract::{Path, RawForm},
+    Extension,
+};
+use bevy::prelude::*;
+use bevy_defer::AsyncWorld;
+use bevy_webserver::RouterAppExt;
+use maud::{html, Markup};
+use serde::de::DeserializeOwned;
+
+use crate::{actions, RconActor, RconAdmin, RconPermission, RconPlayer};
+
+/// The description of a player action, built from its name with `RconPlayerAction::new` or from a `&str`.
+#[derive(Clone)]
+pub struct RconPlayerAction {
+    /// Identifies the action in URLs and the audit log, e.g. `mute`.
+    pub name: String,
+    /// The text of the button. Defaults to the name.
+    pub label: String,
+    /// The permission needed to perform the action. Defaults to `RconPermission::Kick`.
+    pub permission: RconPermission,
+    /// The form fields shown next to the button, deserialized into the event data.
+    pub fields: Vec<RconActionField>,
+}
+
+impl RconPlayerAction {
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self {
+            label: name.clone(),
+            name,
+            permission: RconPermission::Kick,
+            fields: vec![],
+        }
+    }
+
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = label.into();
+        self
+    }
+
+    pub fn permission(mut self, permission: RconPermission) -> Self {
+        self.permission = permission;
+        self
+    }
+
+    /// Adds a text field. Blank fields are left out of the event data, so use an `Option<String>` for optional ones.
+    pub fn text_field(mut self, name: impl Into<String>, label: impl Into<String>) -> Self {
+        self.fields.push(RconActionField {
+            name: name.into(),
+            label: label.into(),
+            kind: RconFieldKind::Text,
+        });
+        self
+    }
+
+    /// Adds a required number field.
+    pub fn number_field(mut self, name: impl Into<String>, label: impl Into<String>) -> Self {
+        self.fields.push(RconActionField {
+            name: name.into(),
+            label: label.into(),
+            kind: RconFieldKind::Number,
+        });
+        self
+    }
+
+    /// Adds a drop-down with the given options, the first one is selected.
+    pub fn select_field(
+        mut self,
+        name: impl Into<String>,
+        label: impl Into<String>,
+        options: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.fields.push(RconActionField {
+            name: name.into(),
+            label: label.into(),
+            kind: RconFieldKind::Select(options.into_iter().map(Into::into).collect()),
+        });
+        self
+    }
+}
+
+impl From<&str> for RconPlayerAction {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<String> for RconPlayerAction {
+    fn from(name: String) -> Self {
+        Self::new(name)
+    }
+}
+
+/// A form field of a player action.
+#[derive(Clone)]
+pub struct RconActionField {
+    /// The name of the field in the event data.
+    pub name: String,
+    /// The placeholder of the field, or the label of a drop-down.
+    pub label: String,
+    pub kind: RconFieldKind,
+}
+
+#[derive(Clone)]
+pub enum RconFieldKind {
+    Text,
+    Number,
+    Select(Vec<String>),
+}
+
+/// An event that is sent to the plugin user when an admin performs a player action.
+/// `T` is the type the action was registered with, deserialized from the form fields.
+#[derive(Event)]
+pub struct RconPlayerActionEvent<T> {
+    /// The name of the action, as registered.
+    pub action: String,
+    pub player: RconPlayer,
+    /// The admin who performed the action.
+    pub actor: RconActor,
+    /// The player's entity, if player entities are enabled.
+    pub entity: Option<Entity>,
+    pub data: T,
+}
+
+/// A resource that contains the registered player actions, in the order they were added.
+#[derive(Resource, Clone, Default)]
+pub(crate) struct RconPlayerActions {
+    pub(crate) actions: Vec<RconPlayerAction>,
+}
+
+/// Adds the route of a player action, and lists it for the player list.
+///
+/// # Panics
+/// Panics if an action with the same name was already added.
+pub(crate) fn add_player_action<T>(app: &mut App, action: RconPlayerAction)
+where
+    T: DeserializeOwned + Send + Sync + 'static,
+{
+    let path = format!("/player_action/{}/{{id}}", action.name);
+    let name = action.name.clone();
+    let permission = action.permission;
+
+    app.add_event::<RconPlayerActionEvent<T>>()
+        .world_mut()
+        .get_resource_or_init::<RconPlayerActions>()
+        .actions
+        .push(action);
+
+    app.route(
+        &path,
+        axum::routing::post(
+            move |admin: Extension<RconAdmin>, actor: RconActor, id: Path<String>, form: RawForm| async move {
+                admin.require(permission)?;
+                let id = id.0;
+
+                match parse_form::<T>(&form.0) {
+                    Ok((data, summary)) => {
+                        let action = name.clone();
+                        if let Err(e) = AsyncWorld.run(move |world: &mut World| {
+                            actions::player_action(world, &actor, &action, &id, summary, data)
+                        }) {
+                            warn!("Failed to perform player action {}: {}", name, e);
+                        }
+                    }
+                    Err(e) => warn!("Invalid form for player action {}: {}", name, e),
+                }
+
+                crate::index(admin).await
+            },
+        ),
+    );
+}
+
+/// Deserializes the event data from the form, along with the filled in fields as `name=value` for the audit log.
+/// Blank fields are left out, so that they deserialize as `None`.
+fn parse_form<T: DeserializeOwned>(form: &[u8]) -> Result<(T, Option<String>), serde_urlencoded::de::Error> {
+    let fields: Vec<(String, String)> = serde_urlencoded::from_bytes::<Vec<(String, String)>>(form)?
+        .into_iter()
+        .filter(|(_, value)| !value.trim().is_empty())
+        .collect();
+    // Encoding pairs of strings can not fail.
+    let data = serde_urlencoded::from_str(&serde_urlencoded::to_string(&fields).unwrap_or_default())?;
+
+    let summary = fields
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Ok((data, Some(summary).filter(|summary| !summary.is_empty())))
+}
+
+/// The forms of the actions the admin may perform on the player.
+pub(crate) fn action_forms(player: &RconPlayer, admin: &RconAdmin, actions: &RconPlayerActions) -> Markup {
+    html! {
+        @for action in actions.actions.iter().filter(|action| admin.can(action.permission)) {
+            form
+                hx-post={"/player_action/" (action.name) "/" (player.unique_id)}
+                hx-target="body"
+                hx-swap="innerHTML"
+            {
+                @for field in &action.fields {
+                    @match &field.kind {
+                        RconFieldKind::Text => input type="text" name=(field.name) placeholder=(field.label);,
+                        RconFieldKind::Number => input type="number" step="any" name=(field.name) placeholder=(field.label) required;,
+                        RconFieldKind::Select(options) => select name=(field.name) title=(field.label) {
+                            @for option in options {
+                                option value=(option) { (option) }
+                            }
+                        },
+                    }
+                }
+                button type="submit" { (action.label) }
+            }
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use axum::{http::StatusCode, response::IntoResponse};
+use bevy::prelude::*;
+use maud::html;
+use serde::{Deserialize, Serialize};
+
+/// An action that an admin may or may not be allowed to perform.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Reflect)]
+pub enum RconPermission {
+    ViewPlayers,
+    Kick,
+    Ban,
+    Unban,
+    RunCommands,
+    ViewAudit,
+    ManageTokens,
+    ViewLogs,
+}
+
+impl std::fmt::Display for RconPermission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RconPermission::ViewPlayers => write!(f, "view players"),
+            RconPermission::Kick => write!(f, "kick players"),
+            RconPermission::Ban => write!(f, "ban players"),
+            RconPermission::Unban => write!(f, "unban players"),
+            RconPermission::RunCommands => write!(f, "run commands"),
+            RconPermission::ViewAudit => write!(f, "view the audit log"),
+            RconPermission::ManageTokens => write!(f, "manage API tokens"),
+            RconPermission::ViewLogs => write!(f, "view the server logs"),
+        }
+    }
+}
+
+/// The role of an admin, which decides the permissions they have.
+/// Roles are ordered from least to most privileged.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Reflect)]
+pub enum RconRole {
+    /// Can only look at the panel.
+    #[default]
+    Viewer,
+    /// Can kick, ban and unban players, and view the audit log and the server logs.
+    Moderator,
+    /// Can do everything, including running commands and managing API tokens.
+    SuperAdmin,
+}
+
+impl RconRole {
+    /// All roles, from least to most privileged.
+    pub const ALL: [RconRole; 3] = [RconRole::Viewer, RconRole::Moderator, RconRole::SuperAdmin];
+
+    /// The permissions granted to this role.
+    pub fn permissions(&self) -> &'static [RconPermission] {
+        use RconPermission::*;
+
+        match self {
+            RconRole::Viewer => &[ViewPlayers],
+            RconRole::Moderator => &[ViewPlayers, Kick, Ban, Unban, ViewAudit, ViewLogs],
+            RconRole::SuperAdmin => &[ViewPlayers, Kick, Ban, Unban, RunCommands, ViewAudit, ManageTokens, ViewLogs],
+        }
+    }
+
+    /// Whether this role grants the given permission.
+    pub fn allows(&self, permission: RconPermission) -> bool {
+        self.permissions().contains(&permission)
+    }
+}
+
+impl std::fmt::Display for RconRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RconRole::Viewer => write!(f, "Viewer"),
+            RconRole::Moderator => write!(f, "Moderator"),
+            RconRole::SuperAdmin => write!(f, "Super Admin"),
+        }
+    }
+}
+
+/// The response returned when an admin lacks the permission for a request.
+pub struct RconForbidden(pub RconPermission);
+
+impl IntoResponse for RconForbidden {
+    fn into_response(self) -> axum::response::Response {
+        let markup = html! {
+            p class="error" { "You do not have permission to " (self.0) "." }
+        };
+        (StatusCode::FORBIDDEN, axum::response::Html(markup.into_string())).into_response()
+    }
+}
@@ -0,0 +1,308 @@
+//! A listener for the Valve Source RCON protocol, so that existing RCON clients can manage the server.
+//! See <https://developer.valvesoftware.com/wiki/Source_RCON_Protocol>.
+
+use std::net::{SocketAddr, TcpListener};
+
+use async_io::Async;
+use bevy::{
+    prelude::*,
+    tasks::futures_lite::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+};
+use bevy_defer::{AsyncExtension, AsyncWorld};
+
+use crate::{auth::RconLoginThrottle, RconActor, RconCaller, RconCommands, RconConfig, RconRole};
+
+const SERVERDATA_AUTH: i32 = 3;
+const SERVERDATA_AUTH_RESPONSE: i32 = 2;
+const SERVERDATA_EXECCOMMAND: i32 = 2;
+const SERVERDATA_RESPONSE_VALUE: i32 = 0;
+
+/// The largest body a client may send, and the largest body of a single response packet.
+/// Longer responses are split over multiple packets.
+const MAX_BODY_SIZE: usize = 4096;
+
+/// The configuration of the Source RCON listener, see `RconPlugin::source_rcon`.
+#[derive(Clone)]
+pub struct SourceRconConfig {
+    pub bind: SocketAddr,
+    /// The password clients authenticate with. Authenticated clients can run every command in `RconCommands`.
+    /// The listener does not start with an empty password.
+    pub password: String,
+}
+
+/// The failed Source RCON logins per IP address. Repeated failures delay further attempts from the address,
+/// like failed panel logins delay further attempts for a username.
+#[derive(Resource, Default)]
+pub(crate) struct SourceRconLogins(RconLoginThrottle);
+
+struct Packet {
+    id: i32,
+    kind: i32,
+    body: Vec<u8>,
+}
+
+/// Starts the listener if it is configured.
+pub(crate) fn start_source_rcon(world: &mut World) {
+    let Some(config) = world.resource::<RconConfig>().source_rcon.clone() else {
+        return;
+    };
+    if config.password.is_empty() {
+        error!("Not starting Source RCON on {}, the password is empty", config.bind);
+        return;
+    }
+
+    world.spawn_task(async move {
+        match Async::<TcpListener>::bind(config.bind) {
+            Ok(listener) => {
+                info!("Source RCON listening on {}", config.bind);
+                listen(listener, config.password).await;
+            }
+            Err(e) => error!("Failed to start Source RCON on {}: {}", config.bind, e),
+        }
+        Ok(())
+    });
+}
+
+async fn listen(listener: Async<TcpListener>, password: String) {
+    loop {
+        let (stream, address) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                error!("Failed to accept Source RCON connection: {}", e);
+                continue;
+            }
+        };
+
+        let password = password.clone();
+        AsyncWorld
+            .spawn_task(async move {
+                if let Err(e) = serve_connection(stream, address, &password).await {
+                    debug!("Source RCON connection from {} closed: {}", address, e);
+                }
+            })
+            .detach();
+    }
+}
+
+async fn serve_connection(
+    mut stream: impl AsyncRead + AsyncWrite + Unpin,
+    address: SocketAddr,
+    password: &str,
+) -> std::io::Result<()> {
+    let caller = RconCaller {
+        actor: RconActor {
+            admin: "rcon".to_string(),
+            source_ip: Some(address.ip().to_string()),
+        },
+        role: RconRole::SuperAdmin,
+    };
+    let mut authenticated = false;
+
+    loop {
+        let packet = read_packet(&mut stream).await?;
+
+        match packet.kind {
+            SERVERDATA_AUTH => {
+                // Source servers send an empty response value before the auth response.
+                write_packet(&mut stream, packet.id, SERVERDATA_RESPONSE_VALUE, b"").await?;
+
+                // Counted as failed until the password matched, so parallel connections share the delay.
+                let ip = address.ip().to_canonical().to_string();
+                let started = AsyncWorld.run({
+                    let ip = ip.clone();
+                    move |world: &mut World| world.resource_mut::<SourceRconLogins>().0.start(&ip)
+                });
+                if let Err(delay) = started {
+                    let seconds = delay.as_secs().max(1);
+                    warn!("Delayed Source RCON login from {} for {}s after repeated failures", address, seconds);
+                    write_packet(&mut stream, -1, SERVERDATA_AUTH_RESPONSE, b"").await?;
+                    return Ok(());
+                }
+
+                if constant_time_eq(&packet.body, password.as_bytes()) {
+                    authenticated = true;
+                    AsyncWorld.run(move |world: &mut World| world.resource_mut::<SourceRconLogins>().0.succeed(&ip));
+                    write_packet(&mut stream, packet.id, SERVERDATA_AUTH_RESPONSE, b"").await?;
+                } else {
+                    warn!("Failed Source RCON login from {}", address);
+                    write_packet(&mut stream, -1, SERVERDATA_AUTH_RESPONSE, b"").await?;
+                    return Ok(());
+                }
+            }
+            SERVERDATA_EXECCOMMAND if authenticated => {
+                let command = String::from_utf8_lossy(&packet.body).to_string();
+                let output = AsyncWorld.run({
+                    let caller = caller.clone();
+                    move |world: &mut World| RconCommands::execute(world, &caller, &command)
+                });
+                let output = output.unwrap_or_else(|e| e.to_string());
+
+                if output.is_empty() {
+                    write_packet(&mut stream, packet.id, SERVERDATA_RESPONSE_VALUE, b"").await?;
+                }
+                for chunk in split_output(&output, MAX_BODY_SIZE) {
+                    write_packet(&mut stream, packet.id, SERVERDATA_RESPONSE_VALUE, chunk.as_bytes()).await?;
+                }
+            }
+            SERVERDATA_RESPONSE_VALUE if authenticated => {
+                // Clients send an empty response value after a command to find the end of a multi-packet response.
+                // It is mirrored back, followed by the marker packet that real Source servers send.
+                write_packet(&mut stream, packet.id, SERVERDATA_RESPONSE_VALUE, b"").await?;
+                write_raw_packet(&mut stream, packet.id, SERVERDATA_RESPONSE_VALUE, &[0, 1, 0, 0]).await?;
+            }
+            _ => {
+                write_packet(&mut stream, -1, SERVERDATA_AUTH_RESPONSE, b"").await?;
+                return Ok(());
+            }
+        }
+    }
+}
+
+async fn read_packet(stream: &mut (impl AsyncRead + Unpin)) -> std::io::Result<Packet> {
+    let mut size = [0; 4];
+    stream.read_exact(&mut size).await?;
+    let size = i32::from_le_bytes(size);
+
+    let Ok(size) = usize::try_from(size) else {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "negative packet size"));
+    };
+    // The size covers the ID, the type, the body and the two null terminators.
+    if !(10..=MAX_BODY_SIZE + 10).contains(&size) {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid packet size"));
+    }
+
+    let mut data = vec![0; size];
+    stream.read_exact(&mut data).await?;
+
+    let id = i32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    let kind = i32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+    let body = &data[8..size - 2];
+    let body = body.split(|byte| *byte == 0).next().unwrap_or_default().to_vec();
+
+    Ok(Packet { id, kind, body })
+}
+
+/// Writes a packet with a null terminated body.
+async fn write_packet(
+    stream: &mut (impl AsyncWrite + Unpin),
+    id: i32,
+    kind: i32,
+    body: &[u8],
+) -> std::io::Result<()> {
+    let mut terminated = body.to_vec();
+    terminated.push(0);
+    write_raw_packet(stream, id, kind, &terminated).await
+}
+
+/// Writes a packet with the body as is, followed by the empty string terminator.
+async fn write_raw_packet(
+    stream: &mut (impl AsyncWrite + Unpin),
+    id: i32,
+    kind: i32,
+    body: &[u8],
+) -> std::io::Result<()> {
+    let size = (body.len() + 9) as i32;
+
+    let mut data = Vec::with_capacity(body.len() + 13);
+    data.extend_from_slice(&size.to_le_bytes());
+    data.extend_from_slice(&id.to_le_bytes());
+    data.extend_from_slice(&kind.to_le_bytes());
+    data.extend_from_slice(body);
+    data.push(0);
+
+    stream.write_all(&data).await?;
+    stream.flush().await
+}
+
+/// Splits the output into chunks of at most `max_size` bytes, without splitting a character.
+fn split_output(mut output: &str, max_size: usize) -> Vec<&str> {
+    let mut chunks = vec![];
+    while !output.is_empty() {
+        let mut end = output.len().min(max_size);
+        while !output.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            // A single character longer than the limit is sent on its own.
+            end = output.chars().next().map_or(output.len(), char::len_utf8);
+        }
+        let (chunk, rest) = output.split_at(end);
+        chunks.push(chunk);
+        output = rest;
+    }
+    chunks
+}
+
+/// Compares two byte strings in constant time for equal lengths, so the password can not be guessed byte by byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::tasks::{block_on, futures_lite::io::Cursor};
+
+    use super::*;
+
+    fn raw_packet(size: i32, id: i32, kind: i32, body: &[u8]) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend_from_slice(&size.to_le_bytes());
+        data.extend_from_slice(&id.to_le_bytes());
+        data.extend_from_slice(&kind.to_le_bytes());
+        data.extend_from_slice(body);
+        data
+    }
+
+    #[test]
+    fn reads_a_packet() {
+        let data = raw_packet(14, 7, SERVERDATA_EXECCOMMAND, b"help\0\0");
+        let packet = block_on(read_packet(&mut Cursor::new(data))).unwrap();
+
+        assert_eq!(packet.id, 7);
+        assert_eq!(packet.kind, SERVERDATA_EXECCOMMAND);
+        assert_eq!(packet.body, b"help");
+    }
+
+    #[test]
+    fn rejects_invalid_packet_sizes() {
+        for size in [-1, 0, 9, (MAX_BODY_SIZE + 11) as i32] {
+            let data = raw_packet(size, 1, SERVERDATA_AUTH, b"\0\0");
+            let error = block_on(read_packet(&mut Cursor::new(data))).err().unwrap();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn fails_on_truncated_packets() {
+        let data = raw_packet(14, 1, SERVERDATA_EXECCOMMAND, b"he");
+        assert!(block_on(read_packet(&mut Cursor::new(data))).is_err());
+    }
+
+    #[test]
+    fn written_packets_read_back() {
+        let mut written = vec![];
+        block_on(write_packet(&mut written, 3, SERVERDATA_RESPONSE_VALUE, b"output")).unwrap();
+
+        assert_eq!(written, raw_packet(16, 3, SERVERDATA_RESPONSE_VALUE, b"output\0\0"));
+        let packet = block_on(read_packet(&mut Cursor::new(written))).unwrap();
+        assert_eq!(packet.id, 3);
+        assert_eq!(packet.body, b"output");
+    }
+
+    #[test]
+    fn writes_raw_bodies_as_is() {
+        let mut written = vec![];
+        block_on(write_raw_packet(&mut written, 5, SERVERDATA_RESPONSE_VALUE, &[0, 1, 0, 0])).unwrap();
+
+        assert_eq!(written, raw_packet(13, 5, SERVERDATA_RESPONSE_VALUE, &[0, 1, 0, 0, 0]));
+    }
+
+    #[test]
+    fn splits_output_on_character_boundaries() {
+        assert!(split_output("", 4).is_empty());
+        assert_eq!(split_output("abcdef", 4), ["abcd", "ef"]);
+        // "é" takes two bytes, so the first chunk ends before it instead of in the middle.
+        assert_eq!(split_output("abcé", 4), ["abc", "é"]);
+        assert_eq!(split_output("ééé", 3), ["é", "é", "é"]);
+    }
+}
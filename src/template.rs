@@ -1,26 +1,168 @@
-use maud::{html, Markup, DOCTYPE};
+//! The page layout shared by every panel page.
+//! Studios can brand the default layout with `RconBranding`, or replace it entirely with `RconPlugin::template`.
 
+use std::sync::Arc;
+
+use bevy_defer::{AsyncAccess, AsyncWorld};
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+
+use crate::{assets::static_url, pages::RconPages, RconAdmin, RconConfig, RconPermission};
+
+/// A function that renders a whole page, replacing the default layout. See `RconPlugin::template`.
+pub type RconTemplate = Arc<dyn Fn(TemplateParams) -> Markup + Send + Sync>;
+
+/// Everything a layout needs to render a page.
 pub struct TemplateParams {
     pub tab_title: String,
     pub game_name: String,
     pub server_name: String,
+    /// The username of the logged in admin, if any.
+    pub admin: Option<String>,
+    /// The pages the admin is allowed to open, empty when nobody is logged in.
+    pub nav: Vec<RconLink>,
+    pub branding: RconBranding,
+    /// The scripts and stylesheets the panel needs. Custom layouts must include it in their `head`.
+    pub head: Markup,
     pub content: Markup,
 }
 
+/// A link in the navigation or the footer.
+#[derive(Clone)]
+pub struct RconLink {
+    pub title: String,
+    pub href: String,
+}
+
+impl RconLink {
+    pub fn new(title: impl Into<String>, href: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            href: href.into(),
+        }
+    }
+}
+
+/// Branding for the default layout, set with the `RconPlugin` builder methods.
+#[derive(Clone, Default)]
+pub struct RconBranding {
+    /// The URL of a logo shown next to the game name.
+    pub logo_url: Option<String>,
+    /// A CSS colour for buttons and links, e.g. `#e0513b`. Used in both the light and the dark theme.
+    pub accent_color: Option<String>,
+    /// Links shown at the bottom of every page, e.g. to the studio's moderation guidelines.
+    pub footer_links: Vec<RconLink>,
+}
+
+/// The default layout: a header with the logo and the account, the navigation, the content and the footer.
+/// Follows the system's light or dark preference, which the theme button overrides.
 pub fn base_template(params: TemplateParams) -> Markup {
     html! {
         (DOCTYPE)
-        html {
+        html lang="en" {
             head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width, initial-scale=1";
                 title { (params.tab_title) }
-                script src="https://unpkg.com/htmx.org@1.9.10" {}
-                link href="https://fonts.googleapis.com/css2?family=Poppins:wght@400;600&display=swap" rel="stylesheet" {}
+                (params.head)
+                @if let Some(accent_color) = &params.branding.accent_color {
+                    // Escaped by maud, so a colour can not close the style element.
+                    style { ":root, :root[data-theme] { --rcon-accent: " (accent_color) "; }" }
+                }
             }
             body {
-                h1 { (params.game_name) }
-                h2 { (params.server_name) }
-                (params.content)
+                header class="rcon-header" {
+                    @if let Some(logo_url) = &params.branding.logo_url {
+                        img class="rcon-logo" src=(logo_url) alt=(params.game_name);
+                    }
+                    div class="rcon-titles" {
+                        h1 { (params.game_name) }
+                        h2 { (params.server_name) }
+                    }
+                    div class="rcon-account" {
+                        button type="button" class="rcon-theme-toggle" data-rcon-theme-toggle title="Switch between light and dark" {
+                            "Theme"
+                        }
+                        @if let Some(admin) = &params.admin {
+                            form method="post" action="/logout" {
+                                span { "Logged in as " (admin) }
+                                button type="submit" { "Logout" }
+                            }
+                        }
+                    }
+                }
+                @if !params.nav.is_empty() {
+                    nav class="rcon-nav" {
+                        @for link in &params.nav {
+                            a href=(link.href) { (link.title) }
+                        }
+                    }
+                }
+                main { (params.content) }
+                @if !params.branding.footer_links.is_empty() {
+                    footer class="rcon-footer" {
+                        @for link in &params.branding.footer_links {
+                            a href=(link.href) { (link.title) }
+                        }
+                    }
+                }
             }
         }
     }
 }
+
+/// The scripts and stylesheets every page needs.
+fn head() -> Markup {
+    html! {
+        link rel="stylesheet" href=(static_url("rcon.css"));
+        script src=(static_url("htmx.min.js")) {}
+        script src=(static_url("rcon.js")) {}
+        // Applies the saved theme before the page is drawn, so it does not flash.
+        script { (PreEscaped("document.documentElement.dataset.theme = localStorage.getItem(\"rcon-theme\") || \"\";")) }
+    }
+}
+
+/// The built in and custom pages the admin is allowed to open.
+fn nav_links(admin: &RconAdmin, pages: &RconPages) -> Vec<RconLink> {
+    let builtin = [
+        ("Players", "/", RconPermission::ViewPlayers),
+        ("Console", "/console", RconPermission::ViewPlayers),
+        ("Audit log", "/audit", RconPermission::ViewAudit),
+        ("Server logs", "/logs", RconPermission::ViewLogs),
+        ("API tokens", "/tokens", RconPermission::ManageTokens),
+    ];
+    builtin
+        .into_iter()
+        .filter(|(_, _, permission)| admin.can(*permission))
+        .map(|(title, href, _)| RconLink::new(title, href))
+        .chain(
+            pages
+                .pages
+                .iter()
+                .filter(|registered| admin.can(registered.page.permission))
+                .map(|registered| RconLink::new(registered.page.title.clone(), registered.path.clone())),
+        )
+        .collect()
+}
+
+/// Renders a page with the configured layout, with the navigation for the logged in admin.
+pub(crate) fn render_page(admin: Option<&RconAdmin>, content: Markup) -> axum::response::Html<String> {
+    let config = AsyncWorld.resource::<RconConfig>().cloned().unwrap_or_default();
+    let pages = AsyncWorld.resource::<RconPages>().cloned().unwrap_or_default();
+
+    let params = TemplateParams {
+        tab_title: config.tab_title,
+        game_name: config.game_name,
+        server_name: config.server_name,
+        admin: admin.map(|admin| admin.username.clone()),
+        nav: admin.map(|admin| nav_links(admin, &pages)).unwrap_or_default(),
+        branding: config.branding,
+        head: head(),
+        content,
+    };
+    let markup = match &config.template {
+        Some(template) => template(params),
+        None => base_template(params),
+    };
+
+    axum::response::Html(markup.into_string())
+}
@@ -0,0 +1,249 @@
+use axum::http::{header, HeaderMap};
+use bevy::prelude::*;
+use bevy_defer::{AsyncAccess, AsyncWorld};
+use maud::html;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    audit::{self, format_timestamp, unix_now},
+    template::render_page,
+    RconActor, RconAdmin, RconForbidden, RconPermission, RconRole,
+};
+
+/// The prefix of every token, so that leaked tokens are easy to recognize.
+const TOKEN_PREFIX: &str = "rcon_";
+
+/// A bearer token for the JSON API, stored in the database.
+/// Only a hash of the token is stored, the token itself is shown once when it is created.
+#[derive(Component, Clone, Default, Serialize, Deserialize, PartialEq, Reflect)]
+pub struct DbRconApiToken {
+    pub name: String,
+    /// The first characters of the token, to tell tokens apart without storing them.
+    pub prefix: String,
+    /// The SHA-256 hash of the token, hex encoded.
+    pub token_hash: String,
+    /// The role the token acts as. Never more privileged than the admin that created it.
+    pub role: RconRole,
+    /// The username of the admin that created the token.
+    pub created_by: String,
+    /// Seconds since the unix epoch.
+    pub created_at: u64,
+    /// Seconds since the unix epoch, `None` if the token was never used.
+    pub last_used: Option<u64>,
+    pub revoked: bool,
+}
+
+fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Reads a bearer token from the authorization header.
+pub(crate) fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+}
+
+/// Returns the admin a token acts as, and records that the token was used.
+/// Revoked and unknown tokens are rejected.
+pub(crate) fn authenticate(world: &mut World, token: &str) -> Option<RconAdmin> {
+    let token_hash = hash_token(token);
+    let mut tokens = world.query::<&mut DbRconApiToken>();
+    let mut token = tokens
+        .iter_mut(world)
+        .find(|stored| !stored.revoked && stored.token_hash == token_hash)?;
+
+    token.last_used = Some(unix_now());
+    Some(RconAdmin {
+        username: format!("token:{}", token.name),
+        role: token.role,
+    })
+}
+
+/// Returns the admin a token acts as, without recording that it was used.
+/// Revoked and unknown tokens are rejected.
+pub(crate) fn token_admin(world: &mut World, token: &str) -> Option<RconAdmin> {
+    let token_hash = hash_token(token);
+    let mut tokens = world.query::<&DbRconApiToken>();
+    let token = tokens
+        .iter(world)
+        .find(|stored| !stored.revoked && stored.token_hash == token_hash)?;
+
+    Some(RconAdmin {
+        username: format!("token:{}", token.name),
+        role: token.role,
+    })
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CreateTokenForm {
+    name: String,
+    role: RconRole,
+}
+
+/// Lists the API tokens, with a form to create new ones.
+pub(crate) async fn tokens_page(
+    admin: axum::Extension<RconAdmin>,
+) -> Result<axum::response::Html<String>, RconForbidden> {
+    admin.require(RconPermission::ManageTokens)?;
+    Ok(tokens_markup(&admin, None, None))
+}
+
+/// Creates a token and shows it once.
+pub(crate) async fn create_token(
+    admin: axum::Extension<RconAdmin>,
+    actor: RconActor,
+    form: axum::extract::Form<CreateTokenForm>,
+) -> Result<axum::response::Html<String>, RconForbidden> {
+    admin.require(RconPermission::ManageTokens)?;
+    let CreateTokenForm { name, role } = form.0;
+    let name = name.trim().to_string();
+
+    if name.is_empty() {
+        return Ok(tokens_markup(&admin, None, Some("The token needs a name")));
+    }
+    // Tokens can not be used to gain permissions the admin does not have.
+    if role > admin.role {
+        return Ok(tokens_markup(&admin, None, Some("Tokens can not have a higher role than your own")));
+    }
+
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let secret: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    let token = format!("{}{}", TOKEN_PREFIX, secret);
+
+    let stored = DbRconApiToken {
+        name: name.clone(),
+        prefix: token[..TOKEN_PREFIX.len() + 8].to_string(),
+        token_hash: hash_token(&token),
+        role,
+        created_by: admin.username.clone(),
+        created_at: unix_now(),
+        last_used: None,
+        revoked: false,
+    };
+    AsyncWorld.run(move |world: &mut World| {
+        info!("{} created API token {} as {}", actor.admin, stored.name, stored.role);
+        audit::record(world, &actor, "create_token", None, Some(name));
+        world.spawn(stored);
+    });
+
+    Ok(tokens_markup(&admin, Some(&token), None))
+}
+
+/// Revokes a token, identified by its prefix. Revoked tokens are kept so they still show up in the list.
+pub(crate) async fn revoke_token(
+    admin: axum::Extension<RconAdmin>,
+    actor: RconActor,
+    path: axum::extract::Path<String>,
+) -> Result<axum::response::Html<String>, RconForbidden> {
+    admin.require(RconPermission::ManageTokens)?;
+    let prefix = path.0;
+
+    let revoked = AsyncWorld.run(move |world: &mut World| {
+        let mut tokens = world.query::<&mut DbRconApiToken>();
+        let name = tokens
+            .iter_mut(world)
+            .find(|token| !token.revoked && token.prefix == prefix)
+            .map(|mut token| {
+                token.revoked = true;
+                token.name.clone()
+            });
+
+        if let Some(name) = &name {
+            info!("{} revoked API token {}", actor.admin, name);
+            audit::record(world, &actor, "revoke_token", None, Some(name.clone()));
+        }
+        name.is_some()
+    });
+
+    if !revoked {
+        warn!("Failed to revoke API token: no active token found");
+    }
+
+    Ok(tokens_markup(&admin, None, None))
+}
+
+/// The tokens page. `new_token` is shown once after a token was created.
+fn tokens_markup(admin: &RconAdmin, new_token: Option<&str>, error: Option<&str>) -> axum::response::Html<String> {
+    let mut tokens = AsyncWorld
+        .query::<&DbRconApiToken>()
+        .get_mut(|mut tokens| tokens.iter().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+    tokens.sort_by_key(|token| std::cmp::Reverse(token.created_at));
+
+    let content = html! {
+        h3 { "API Tokens" }
+        p { "Send a token as " code { "Authorization: Bearer <token>" } " to use the JSON API under " code { "/api/v1" } "." }
+
+        @if let Some(error) = error {
+            p class="error" { (error) }
+        }
+        @if let Some(token) = new_token {
+            div class="new-token" {
+                p { "Copy the new token now, it will not be shown again:" }
+                code { (token) }
+            }
+        }
+
+        form hx-post="/tokens" hx-target="body" hx-swap="innerHTML" {
+            input type="text" name="name" placeholder="Name, e.g. Discord bot" required;
+            select name="role" {
+                @for role in RconRole::ALL.into_iter().filter(|role| *role <= admin.role) {
+                    option value=(format!("{:?}", role)) { (role) }
+                }
+            }
+            button type="submit" { "Create token" }
+        }
+
+        table class="api-tokens" {
+            thead {
+                tr {
+                    th { "Name" }
+                    th { "Token" }
+                    th { "Role" }
+                    th { "Created" }
+                    th { "Last used" }
+                    th {}
+                }
+            }
+            tbody {
+                @for token in tokens {
+                    tr {
+                        td { (token.name) }
+                        td { code { (token.prefix) "…" } }
+                        td { (token.role) }
+                        td { (format_timestamp(token.created_at)) " by " (token.created_by) }
+                        td {
+                            @match token.last_used {
+                                Some(last_used) => (format_timestamp(last_used)),
+                                None => "Never",
+                            }
+                        }
+                        td {
+                            @if token.revoked {
+                                "Revoked"
+                            } @else {
+                                button
+                                    hx-post={"/tokens/" (token.prefix) "/revoke"}
+                                    hx-target="body"
+                                    hx-swap="innerHTML"
+                                    hx-confirm={"Revoke " (token.name) "?"}
+                                    { "Revoke" }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    render_page(Some(admin), content)
+}
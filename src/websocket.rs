@@ -0,0 +1,413 @@
+//! A WebSocket endpoint at `/ws` for interactive administration.
+//! Clients send command lines as text messages, and receive JSON messages with the command output
+//! and with player joins, leaves, kicks, bans and unbans as they happen.
+//!
+//! The webserver can not upgrade connections, so the endpoint has its own listener, see `RconPlugin::websocket`.
+//! Clients authenticate with the panel session cookie, an `Authorization: Bearer` header,
+//! or a `token` query parameter for clients that can not set headers.
+//! Cookie logins are only accepted from pages on the panel's host, and the session or token is checked
+//! again before every command and every event sent, so the connection is closed once the admin logs out
+//! or the token is revoked.
+
+use std::net::{SocketAddr, TcpListener, TcpStream};
+
+use async_io::Async;
+use async_tungstenite::tungstenite::{
+    handshake::server::{ErrorResponse, Request, Response},
+    http::{header, StatusCode},
+    protocol::{frame::coding::CloseCode, CloseFrame},
+    Message,
+};
+use bevy::{
+    ecs::system::SystemState,
+    prelude::*,
+    tasks::futures_lite::{future, StreamExt},
+};
+use bevy_defer::{AsyncExtension, AsyncWorld};
+use serde::Serialize;
+
+use crate::{
+    auth::{session_token, RconCredential},
+    tokens, RconActor, RconAdmin, RconCaller, RconCommands, RconConfig, RconPermission, RconPlayer,
+    RconPlayerBanned, RconPlayerKicked, RconPlayerUnbanned, RconPlayers, RconRole,
+};
+
+/// How many messages are queued for a client that is not reading, further messages are dropped.
+const CLIENT_QUEUE_SIZE: usize = 64;
+
+/// A message sent to WebSocket clients, as JSON with a `type` field.
+#[derive(Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum WsMessage {
+    /// The output of a command the client sent.
+    Output { command: String, output: String },
+    /// The error of a command the client sent.
+    Error { command: String, error: String },
+    PlayerJoined { player: RconPlayer },
+    PlayerLeft { player: RconPlayer },
+    PlayerKicked { player: RconPlayer, reason: Option<String> },
+    PlayerBanned { player: RconPlayer, expires_at: Option<u64>, reason: Option<String> },
+    PlayerUnbanned { player: RconPlayer },
+}
+
+impl WsMessage {
+    /// The message as an admin with the role may see it, see `RconPlayer::visible_to`.
+    fn visible_to(self, role: RconRole) -> Self {
+        match self {
+            WsMessage::PlayerJoined { player } => WsMessage::PlayerJoined { player: player.visible_to(role) },
+            WsMessage::PlayerLeft { player } => WsMessage::PlayerLeft { player: player.visible_to(role) },
+            WsMessage::PlayerKicked { player, reason } => WsMessage::PlayerKicked {
+                player: player.visible_to(role),
+                reason,
+            },
+            WsMessage::PlayerBanned { player, expires_at, reason } => WsMessage::PlayerBanned {
+                player: player.visible_to(role),
+                expires_at,
+                reason,
+            },
+            WsMessage::PlayerUnbanned { player } => WsMessage::PlayerUnbanned { player: player.visible_to(role) },
+            message @ (WsMessage::Output { .. } | WsMessage::Error { .. }) => message,
+        }
+    }
+}
+
+/// A resource that contains the connected WebSocket clients.
+#[derive(Resource, Default)]
+pub(crate) struct RconWsClients {
+    clients: Vec<WsClient>,
+}
+
+struct WsClient {
+    sender: async_channel::Sender<WsMessage>,
+    /// Checked before every message, the client is dropped once it is no longer valid.
+    credential: RconCredential,
+}
+
+type WsEventParams = (
+    Res<'static, RconPlayers>,
+    Local<'static, Vec<RconPlayer>>,
+    EventReader<'static, 'static, RconPlayerKicked>,
+    EventReader<'static, 'static, RconPlayerBanned>,
+    EventReader<'static, 'static, RconPlayerUnbanned>,
+);
+
+/// Sends player joins and leaves, and the kick, ban and unban events, to every connected client
+/// whose session or token is still valid, as its admin's current role may see them.
+/// The other clients are dropped, which closes their connection.
+pub(crate) fn broadcast_ws_events(world: &mut World, events: &mut SystemState<WsEventParams>) {
+    let (players, mut known_players, mut kicked, mut banned, mut unbanned) = events.get_mut(world);
+    let mut messages = vec![];
+
+    if players.is_changed() {
+        // Compared by ID, so that metadata changes like the ping are not reported as joins.
+        let is_in = |list: &[RconPlayer], player: &RconPlayer| list.iter().any(|other| other.unique_id == player.unique_id);
+        for player in players.players.iter().filter(|player| !is_in(&known_players, player)) {
+            messages.push(WsMessage::PlayerJoined { player: player.clone() });
+        }
+        for player in known_players.iter().filter(|player| !is_in(&players.players, player)) {
+            messages.push(WsMessage::PlayerLeft { player: player.clone() });
+        }
+        *known_players = players.players.clone();
+    }
+
+    for event in kicked.read() {
+        messages.push(WsMessage::PlayerKicked {
+            player: event.player.clone(),
+            reason: event.reason.clone(),
+        });
+    }
+    for event in banned.read() {
+        messages.push(WsMessage::PlayerBanned {
+            player: event.player.clone(),
+            expires_at: event.expires_at,
+            reason: event.reason.clone(),
+        });
+    }
+    for event in unbanned.read() {
+        messages.push(WsMessage::PlayerUnbanned { player: event.player.clone() });
+    }
+
+    if messages.is_empty() || world.resource::<RconWsClients>().clients.is_empty() {
+        return;
+    }
+
+    let mut clients = std::mem::take(&mut world.resource_mut::<RconWsClients>().clients);
+    clients.retain(|client| {
+        let Some(admin) = client.credential.admin(world) else {
+            return false;
+        };
+        messages.iter().all(|message| match client.sender.try_send(message.clone().visible_to(admin.role)) {
+            Ok(()) | Err(async_channel::TrySendError::Full(_)) => true,
+            Err(async_channel::TrySendError::Closed(_)) => false,
+        })
+    });
+    // Clients that connected meanwhile are kept.
+    world.resource_mut::<RconWsClients>().clients.extend(clients);
+}
+
+/// Starts the listener if it is configured.
+pub(crate) fn start_websocket(world: &mut World) {
+    let Some(bind) = world.resource::<RconConfig>().websocket else {
+        return;
+    };
+
+    world.spawn_task(async move {
+        match Async::<TcpListener>::bind(bind) {
+            Ok(listener) => {
+                info!("RCON WebSocket listening on ws://{}/ws", bind);
+                listen(listener).await;
+            }
+            Err(e) => error!("Failed to start RCON WebSocket on {}: {}", bind, e),
+        }
+        Ok(())
+    });
+}
+
+async fn listen(listener: Async<TcpListener>) {
+    loop {
+        let (stream, address) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                error!("Failed to accept RCON WebSocket connection: {}", e);
+                continue;
+            }
+        };
+
+        AsyncWorld
+            .spawn_task(async move {
+                if let Err(e) = serve_connection(stream, address).await {
+                    debug!("RCON WebSocket connection from {} closed: {}", address, e);
+                }
+            })
+            .detach();
+    }
+}
+
+/// What the connection is waiting for.
+enum Incoming {
+    Client(Option<Result<Message, async_tungstenite::tungstenite::Error>>),
+    Broadcast(Option<WsMessage>),
+}
+
+async fn serve_connection(
+    stream: Async<TcpStream>,
+    address: SocketAddr,
+) -> Result<(), async_tungstenite::tungstenite::Error> {
+    let mut login = None;
+    // The error type is dictated by the handshake callback.
+    #[allow(clippy::result_large_err)]
+    let websocket = async_tungstenite::accept_hdr_async(stream, |request: &Request, response: Response| {
+        if request.uri().path() != "/ws" {
+            return Err(error_response(StatusCode::NOT_FOUND, "Not found"));
+        }
+        login = authenticate(request).filter(|(admin, _)| admin.can(RconPermission::ViewPlayers));
+        match &login {
+            Some((_, RconCredential::Session(_))) if !is_same_host(request) => {
+                Err(error_response(StatusCode::FORBIDDEN, "Cross-origin WebSocket connections are not allowed"))
+            }
+            Some(_) => Ok(response),
+            None => Err(error_response(StatusCode::UNAUTHORIZED, "Not logged in, or invalid API token")),
+        }
+    })
+    .await?;
+    let Some((admin, credential)) = login else {
+        return Ok(());
+    };
+
+    info!("RCON admin {} connected over WebSocket from {}", admin.username, address);
+    let actor = RconActor {
+        admin: admin.username,
+        source_ip: Some(address.ip().to_string()),
+    };
+
+    let (sender, broadcasts) = async_channel::bounded(CLIENT_QUEUE_SIZE);
+    let client = WsClient {
+        sender,
+        credential: credential.clone(),
+    };
+    AsyncWorld.run(|world: &mut World| world.resource_mut::<RconWsClients>().clients.push(client));
+
+    let (mut outgoing, mut incoming) = websocket.split();
+    loop {
+        let next = future::or(
+            async { Incoming::Client(incoming.next().await) },
+            async { Incoming::Broadcast(broadcasts.recv().await.ok()) },
+        )
+        .await;
+
+        let reply = match next {
+            Incoming::Client(Some(Ok(Message::Text(line)))) => {
+                let command = line.to_string();
+                let credential = credential.clone();
+                let actor = actor.clone();
+                let reply = AsyncWorld.run(move |world: &mut World| {
+                    // The role is read again too, so role changes apply to open connections.
+                    let admin = credential.admin(world)?;
+                    let caller = RconCaller { actor, role: admin.role };
+                    let reply = match RconCommands::execute(world, &caller, &command) {
+                        Ok(output) => WsMessage::Output { command, output },
+                        Err(e) => WsMessage::Error { command, error: e.to_string() },
+                    };
+                    Some(reply)
+                });
+                match reply {
+                    Some(reply) => reply,
+                    None => return close_revoked(&mut outgoing, address).await,
+                }
+            }
+            Incoming::Client(Some(Ok(Message::Close(_)))) | Incoming::Client(None) => return Ok(()),
+            // Pings are answered by the WebSocket implementation.
+            Incoming::Client(Some(Ok(_))) => continue,
+            Incoming::Client(Some(Err(e))) => return Err(e),
+            // Broadcasts are already checked and redacted for the client.
+            Incoming::Broadcast(Some(message)) => message,
+            // Only happens when `broadcast_ws_events` dropped the client.
+            Incoming::Broadcast(None) => return close_revoked(&mut outgoing, address).await,
+        };
+
+        match serde_json::to_string(&reply) {
+            Ok(json) => outgoing.send(Message::text(json)).await?,
+            Err(e) => error!("Failed to serialize WebSocket message: {}", e),
+        }
+    }
+}
+
+/// Closes a connection whose session ended or whose token was revoked.
+async fn close_revoked(
+    outgoing: &mut async_tungstenite::WebSocketSender<Async<TcpStream>>,
+    address: SocketAddr,
+) -> Result<(), async_tungstenite::tungstenite::Error> {
+    info!("Closing RCON WebSocket connection from {}, its session or token is no longer valid", address);
+    let close = CloseFrame {
+        code: CloseCode::Policy,
+        reason: "Session expired or token revoked".into(),
+    };
+    outgoing.close(Some(close)).await
+}
+
+/// Finds the admin for the session cookie, or the API token in the authorization header or query.
+fn authenticate(request: &Request) -> Option<(RconAdmin, RconCredential)> {
+    let session = session_token(request.headers()).map(RconCredential::Session);
+    let token = tokens::bearer_token(request.headers())
+        .or_else(|| {
+            request
+                .uri()
+                .query()
+                .into_iter()
+                .flat_map(|query| query.split('&'))
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(name, _)| *name == "token")
+                .map(|(_, token)| token.to_string())
+        })
+        .map(RconCredential::Token);
+
+    AsyncWorld.run(move |world: &mut World| {
+        session
+            .into_iter()
+            .chain(token)
+            .find_map(|credential| Some((credential.admin(world)?, credential)))
+    })
+}
+
+/// Whether the `Origin` header names the host the connection was made to. The port is ignored,
+/// because the WebSocket listens on a different port than the panel whose page opens it.
+/// Browsers always send the header, so a missing one is rejected too.
+fn is_same_host(request: &Request) -> bool {
+    let header = |name| request.headers().get(name).and_then(|value| value.to_str().ok());
+    let (Some(origin), Some(host)) = (header(header::ORIGIN), header(header::HOST)) else {
+        return false;
+    };
+    let Some((_, origin)) = origin.split_once("://") else {
+        return false;
+    };
+    host_name(origin).eq_ignore_ascii_case(host_name(host))
+}
+
+/// The host of an authority like `example.com:8080` or `[::1]:8080`, without the port.
+fn host_name(authority: &str) -> &str {
+    match authority.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or(rest),
+        None => authority.split(':').next().unwrap_or(authority),
+    }
+}
+
+fn error_response(status: StatusCode, message: &str) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(message.to_string()));
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::auth::RconSessions;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.init_resource::<RconPlayers>()
+            .init_resource::<RconSessions>()
+            .init_resource::<RconWsClients>()
+            .add_event::<RconPlayerKicked>()
+            .add_event::<RconPlayerBanned>()
+            .add_event::<RconPlayerUnbanned>()
+            .add_systems(Update, broadcast_ws_events);
+        app
+    }
+
+    fn connect(app: &mut App, role: RconRole) -> (String, async_channel::Receiver<WsMessage>) {
+        let admin = RconAdmin {
+            username: "admin".to_string(),
+            role,
+        };
+        let session = app
+            .world_mut()
+            .resource_mut::<RconSessions>()
+            .create(admin, Duration::from_secs(60));
+        let (sender, receiver) = async_channel::bounded(CLIENT_QUEUE_SIZE);
+        let client = WsClient {
+            sender,
+            credential: RconCredential::Session(session.clone()),
+        };
+        app.world_mut().resource_mut::<RconWsClients>().clients.push(client);
+        (session, receiver)
+    }
+
+    fn join(app: &mut App, unique_id: &str) {
+        let player = RconPlayer::new(unique_id, "Alice").with_ip("203.0.113.7".parse().unwrap());
+        app.world_mut().resource_mut::<RconPlayers>().players.push(player);
+        app.update();
+    }
+
+    #[test]
+    fn sends_players_as_the_current_role_may_see_them() {
+        let mut app = app();
+        let (_, viewer) = connect(&mut app, RconRole::Viewer);
+        let (_, moderator) = connect(&mut app, RconRole::Moderator);
+        join(&mut app, "steam_1");
+
+        let Ok(WsMessage::PlayerJoined { player }) = viewer.try_recv() else {
+            panic!("the viewer was not told about the join");
+        };
+        assert_eq!(player.ip, None);
+        let Ok(WsMessage::PlayerJoined { player }) = moderator.try_recv() else {
+            panic!("the moderator was not told about the join");
+        };
+        assert!(player.ip.is_some());
+    }
+
+    #[test]
+    fn drops_clients_whose_session_ended() {
+        let mut app = app();
+        let (session, client) = connect(&mut app, RconRole::SuperAdmin);
+        let (_, other) = connect(&mut app, RconRole::SuperAdmin);
+        app.world_mut().resource_mut::<RconSessions>().remove(&session);
+        join(&mut app, "steam_1");
+
+        assert!(client.try_recv().is_err());
+        assert!(client.is_closed());
+        assert!(other.try_recv().is_ok());
+        assert_eq!(app.world().resource::<RconWsClients>().clients.len(), 1);
+    }
+}